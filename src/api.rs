@@ -0,0 +1,1421 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use clap::ValueEnum;
+use futures::stream::{self, StreamExt};
+use reqwest::{Certificate, Client, header};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::time::{sleep, Duration};
+
+use crate::archive::ResponseArchive;
+use crate::breaker::CircuitBreaker;
+use crate::error::{root_cause, PingdomError};
+use crate::dates::{self, Period};
+use crate::maintenance;
+use crate::report::{uptime_percentage, BucketUptime, DowntimeRounding, OutageDistribution, PeriodUptime, ProbeUptime, TrendPoint, UptimeResult};
+
+/// Tag names of a check from the `/checks` listing.
+pub fn check_tags(check: &Value) -> impl Iterator<Item = &str> {
+    check["tags"].as_array().into_iter().flatten().filter_map(|t| t["name"].as_str())
+}
+
+/// How many times a request is retried after Pingdom answers 429 Too Many Requests.
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+/// With fewer requests than this left in a `Req-Limit-Short`/`Req-Limit-Long` window, requests
+/// are spread out over the time until it resets instead of running into a 429.
+const QUOTA_SLOWDOWN_BELOW: u64 = 50;
+
+/// Longest delay put before a single request while slowing down.
+const QUOTA_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Longest wait for a used-up window to reset. A window further from its reset (the long-term
+/// one, say) is not waited for; its requests go ahead and the 429 handling takes over.
+const QUOTA_MAX_PAUSE: Duration = Duration::from_secs(15 * 60);
+
+/// Default cap on a single response body: far above any sane summary, low enough for a small
+/// container.
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// The largest `limit` Pingdom accepts on `/checks`.
+pub const MAX_CHECKS_PAGE_SIZE: usize = 25000;
+
+/// `/checks` query parameters that `--checks-endpoint-params` may pass through. They narrow
+/// or annotate the list without changing the shape of the response; `limit`, `offset` and
+/// `include_tags` are left out because the paging and the tag filters depend on them.
+pub const CHECKS_ENDPOINT_PARAMS: &[&str] = &["tags", "include_severity", "showencryption"];
+
+/// reqwest's own idle timeout for pooled connections.
+pub const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// How many per-probe summaries `calculate_probe_uptime` requests at the same time.
+const PROBE_CONCURRENCY: usize = 5;
+
+/// Check types `summary.performance` reports uptime for. Anything else, such as transaction
+/// checks, has no meaningful uptime summary.
+const UPTIME_CHECK_TYPES: &[&str] = &["http", "httpcustom", "tcp", "ping", "dns", "udp", "smtp", "pop3", "imap"];
+
+/// The check's type. The `/checks` listing has it as a string, the check detail as an object
+/// keyed by the type name.
+pub fn check_type(check: &Value) -> Option<&str> {
+    match &check["type"] {
+        Value::String(name) => Some(name),
+        Value::Object(types) => types.keys().next().map(String::as_str),
+        _ => None,
+    }
+}
+
+/// What the check monitors. The `/checks` listing only has the hostname; a check detail's
+/// type-specific object adds the URL of HTTP checks, the port of TCP/UDP checks and the
+/// nameserver DNS checks query (as `hostname@nameserver`). A `host` in that object, which TCP
+/// and ping checks may carry, takes the place of the hostname.
+pub fn check_target(check: &Value) -> Option<String> {
+    let non_empty = |value: &Value| value.as_str().filter(|v| !v.is_empty()).map(str::to_string);
+    let (Some(kind), true) = (check_type(check), check["type"].is_object()) else {
+        return non_empty(&check["hostname"]);
+    };
+    let detail = &check["type"][kind];
+    let hostname = non_empty(&detail["host"]).or_else(|| non_empty(&check["hostname"]))?;
+    Some(match (kind, detail["url"].as_str(), detail["port"].as_u64()) {
+        ("dns", _, _) => match non_empty(&detail["nameserver"]) {
+            Some(nameserver) => format!("{}@{}", hostname, nameserver),
+            None => hostname,
+        },
+        ("http" | "httpcustom", url, port) => {
+            let scheme = if detail["encryption"].as_bool() == Some(true) { "https" } else { "http" };
+            let port = port.filter(|p| !matches!((scheme, p), ("https", 443) | ("http", 80)));
+            let port = port.map(|p| format!(":{}", p)).unwrap_or_default();
+            format!("{}://{}{}{}", scheme, hostname, port, url.unwrap_or_default())
+        }
+        (_, _, Some(port)) => format!("{}:{}", hostname, port),
+        _ => hostname,
+    })
+}
+
+pub fn is_uptime_check(check: &Value) -> bool {
+    check_type(check).is_some_and(|t| UPTIME_CHECK_TYPES.contains(&t))
+}
+
+#[derive(Clone)]
+pub struct PingdomApi {
+    pingdom_uri: String,
+    client: Client,
+    verbose: bool,
+    checks_page_size: usize,
+    checks_params: Vec<(String, String)>,
+    max_payload_bytes: usize,
+    archive: Option<Arc<ResponseArchive>>,
+    account: Option<String>,
+    counters: Arc<Counters>,
+    /// Latest time from the `Date` header of a response, as epoch seconds; 0 until one arrives
+    server_clock: Arc<AtomicI64>,
+    retry_budget: Option<Arc<RetryBudget>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// What the `Req-Limit-*` headers of the latest response said is left of the key's quota
+    quota: Arc<Mutex<Quota>>,
+}
+
+/// The request quota of an API key, from Pingdom's `Req-Limit-Short` (hourly) and
+/// `Req-Limit-Long` (monthly) headers. Either is `None` until a response carries it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quota {
+    pub short: Option<QuotaWindow>,
+    pub long: Option<QuotaWindow>,
+    /// Reset time of the used-up window the last pause waited for, so it is announced once
+    announced_pause: Option<Instant>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QuotaWindow {
+    pub remaining: u64,
+    pub resets_at: Instant,
+}
+
+/// Parses a `Req-Limit-*` header such as `Remaining: 394 Time until reset: 3589`.
+fn parse_req_limit(value: &str) -> Option<(u64, u64)> {
+    let number_after = |label: &str| {
+        let (_, rest) = value.split_once(label)?;
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    Some((number_after("Remaining:")?, number_after("reset:")?))
+}
+
+/// Shared by every clone of the client, so concurrent checks add to the same totals.
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    retries: AtomicU64,
+    rate_limited: AtomicU64,
+    throttled: AtomicU64,
+    check_retries: AtomicU64,
+}
+
+/// Totals of the requests a client has made so far.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestStats {
+    pub requests: u64,
+    pub retries: u64,
+    /// Responses with status 429 Too Many Requests
+    pub rate_limited: u64,
+    /// Requests delayed because little was left of the quota
+    pub throttled: u64,
+    /// Whole-check recalculations made by `--probe-timeout-retries`
+    pub check_retries: u64,
+}
+
+impl std::ops::Add for RequestStats {
+    type Output = RequestStats;
+
+    fn add(self, other: RequestStats) -> RequestStats {
+        RequestStats {
+            requests: self.requests + other.requests,
+            retries: self.retries + other.retries,
+            rate_limited: self.rate_limited + other.rate_limited,
+            throttled: self.throttled + other.throttled,
+            check_retries: self.check_retries + other.check_retries,
+        }
+    }
+}
+
+/// `--retry-budget`: how many retries the whole run may make, 429 retries and
+/// `--probe-timeout-retries` alike, shared by every account and check. Once it is spent,
+/// failures surface straight away instead of adding load to an API that is already struggling.
+#[derive(Debug)]
+pub struct RetryBudget {
+    limit: u64,
+    used: AtomicU64,
+    warned: AtomicBool,
+}
+
+impl RetryBudget {
+    pub fn new(limit: u64) -> Self {
+        RetryBudget { limit, used: AtomicU64::new(0), warned: AtomicBool::new(false) }
+    }
+
+    /// Takes one retry from the budget, or says (once) that it is spent and returns false.
+    pub fn take(&self) -> bool {
+        let taken = self.used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| (used < self.limit).then_some(used + 1)).is_ok();
+        if !taken && !self.warned.swap(true, Ordering::Relaxed) {
+            eprintln!("Warning: the --retry-budget of {} retries is spent; failing requests are no longer retried", self.limit);
+        }
+        taken
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+/// For `--fail-fast`: describes `e` when Pingdom could not be reached at all (connection
+/// refused, DNS or TLS failure, ...), which no other check would get past either.
+pub fn connection_failure(e: &(dyn Error + 'static)) -> Option<String> {
+    let Some(PingdomError::Network(e)) = e.downcast_ref::<PingdomError>().filter(|e| e.is_connect()) else {
+        return None;
+    };
+    let origin = e.url().map(|u| u.origin().ascii_serialization()).unwrap_or_else(|| "the API".to_string());
+    Some(format!("could not connect to {}: {}", origin, root_cause(e)))
+}
+
+/// What the circuit breaker counts as a failed request: no response at all, a 5xx, or a 429.
+/// Other error statuses, like a rejected key, mean the API itself is answering.
+fn breaker_failure(response: &Result<reqwest::Response, reqwest::Error>) -> Option<String> {
+    match response {
+        Err(e) => Some(root_cause(e).to_string()),
+        Ok(r) if r.status().is_server_error() || r.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            Some(format!("HTTP {}", r.status().as_u16()))
+        }
+        Ok(_) => None,
+    }
+}
+
+/// Strips any credentials embedded in the URL so it is safe to log. The API key itself travels
+/// in the Authorization header, which is never logged.
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) => {
+            if !parsed.username().is_empty() {
+                let _ = parsed.set_username("REDACTED");
+            }
+            if parsed.password().is_some() {
+                let _ = parsed.set_password(Some("REDACTED"));
+            }
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// How the HTTP client talks to the API, beyond the key and base URL.
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    pub verbose: bool,
+    /// Header carrying the API key, `Authorization` for Pingdom itself
+    pub auth_header_name: String,
+    /// Prefix before the key in the header value; empty sends the bare key
+    pub auth_scheme: String,
+    /// Accept gzip, deflate and brotli encoded responses
+    pub compression: bool,
+    /// `limit` of each `/checks` request; larger accounts are fetched in several pages
+    pub checks_page_size: usize,
+    /// Extra `/checks` query parameters, from `CHECKS_ENDPOINT_PARAMS`
+    pub checks_params: Vec<(String, String)>,
+    /// Largest response body read before the request fails
+    pub max_payload_bytes: usize,
+    /// Idle connections kept open per host; `None` keeps reqwest's default (no limit)
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept before it is closed
+    pub pool_idle_timeout: Duration,
+    /// Where `--archive-dir` keeps the raw responses of this run
+    pub archive: Option<Arc<ResponseArchive>>,
+    /// Account name recorded next to archived responses
+    pub account: Option<String>,
+    /// Skip TLS certificate and hostname verification (`--insecure`)
+    pub accept_invalid_certs: bool,
+    /// PEM file of extra root certificates to trust, e.g. a proxy's internal CA
+    pub ca_bundle: Option<PathBuf>,
+    /// Cap on the retries of the whole run, shared by every client it is given to
+    pub retry_budget: Option<Arc<RetryBudget>>,
+    /// Stops sending requests while too many recent ones fail, shared like the retry budget
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            verbose: false,
+            auth_header_name: "Authorization".to_string(),
+            auth_scheme: "Bearer".to_string(),
+            compression: true,
+            checks_page_size: MAX_CHECKS_PAGE_SIZE,
+            checks_params: Vec::new(),
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: Duration::from_secs(DEFAULT_POOL_IDLE_TIMEOUT_SECS),
+            archive: None,
+            account: None,
+            accept_invalid_certs: false,
+            ca_bundle: None,
+            retry_budget: None,
+            circuit_breaker: None,
+        }
+    }
+}
+
+/// Reads the certificates of a `--ca-bundle`, failing when the file holds none.
+fn load_ca_bundle(path: &Path) -> Result<Vec<Certificate>, PingdomError> {
+    let pem = std::fs::read(path).map_err(|source| PingdomError::Io {
+        context: format!("could not read CA bundle {}", path.display()),
+        source,
+    })?;
+    let certificates = Certificate::from_pem_bundle(&pem).map_err(|e| {
+        PingdomError::Config(match e.source() {
+            Some(cause) => format!("CA bundle {} is not valid PEM: {}", path.display(), cause),
+            None => format!("CA bundle {} is not valid PEM", path.display()),
+        })
+    })?;
+    if certificates.is_empty() {
+        return Err(PingdomError::Config(format!("CA bundle {} contains no PEM certificates", path.display())));
+    }
+    Ok(certificates)
+}
+
+/// Bucket size of `summary.performance`. The uptime totals are the same at every resolution;
+/// finer buckets only mean larger responses.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Hour,
+    Day,
+    Week,
+}
+
+impl Resolution {
+    /// The `resolution` query parameter.
+    pub fn param(self) -> &'static str {
+        match self {
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+            Resolution::Week => "week",
+        }
+    }
+
+    /// Key of the bucket array in the response: `hours`, `days` or `weeks`.
+    fn buckets_key(self) -> &'static str {
+        match self {
+            Resolution::Hour => "hours",
+            Resolution::Day => "days",
+            Resolution::Week => "weeks",
+        }
+    }
+
+    pub fn seconds(self) -> i64 {
+        match self {
+            Resolution::Hour => 3600,
+            Resolution::Day => 86400,
+            Resolution::Week => 7 * 86400,
+        }
+    }
+
+    /// Most buckets asked for in one request before the response gets unreasonably large: a week of
+    /// hours, a month of days or a year of weeks.
+    pub fn max_buckets(self) -> i64 {
+        match self {
+            Resolution::Hour => 168,
+            Resolution::Day => 31,
+            Resolution::Week => 53,
+        }
+    }
+}
+
+/// What `calculate_uptime` fetches and how it rounds, beyond the check and its window.
+#[derive(Clone, Debug)]
+pub struct UptimeOptions {
+    pub with_outages: bool,
+    pub downtime_rounding: DowntimeRounding,
+    pub resolution: Resolution,
+    /// Send `includeuptime=true`; without it the buckets only carry response times
+    pub include_uptime: bool,
+    /// Comma-separated probe ids the performance summary is restricted to; `None` uses every probe
+    pub probes: Option<String>,
+    /// Keep the figures of every bucket in the result, for `--with-weekly-breakdown`
+    pub breakdown: bool,
+    /// `--maintenance` windows within the report window, as merged Unix time intervals
+    pub maintenance: Vec<(i64, i64)>,
+    /// Count only the downtime inside the outage summary's down states, for `--exclude-unconfirmed`
+    pub exclude_unconfirmed: bool,
+}
+
+/// Percent-encodes a query parameter value, leaving the characters Pingdom's lists use (`,`)
+/// readable in `--verbose` output.
+fn query_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b',' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// How much of an unexpected body is quoted in the error.
+const BODY_SNIPPET_LEN: usize = 200;
+
+/// Parses a response body as JSON. A response whose content type is not JSON (an HTML error
+/// page from a proxy, say) is not parsed at all; without a content type the body has to look
+/// like JSON. Either, and bodies that fail to parse, e.g. because they were cut off, become an
+/// error with the status code and the start of the body instead of a bare serde message.
+fn parse_json_body(url: &str, status: reqwest::StatusCode, content_type: &str, body: &[u8]) -> Result<Value, PingdomError> {
+    let describe = |problem: String| {
+        let text = String::from_utf8_lossy(body);
+        let mut snippet: String = text.chars().take(BODY_SNIPPET_LEN).collect();
+        if text.chars().count() > BODY_SNIPPET_LEN {
+            snippet.push_str("...");
+        }
+        format!(
+            "{} from {} (HTTP {}, content type '{}'): {}",
+            problem,
+            redact_url(url),
+            status.as_u16(),
+            content_type,
+            snippet.trim()
+        )
+    };
+
+    let looks_like_json = if content_type.is_empty() {
+        matches!(body.iter().find(|b| !b.is_ascii_whitespace()), Some(b'{') | Some(b'['))
+    } else {
+        content_type.to_ascii_lowercase().contains("json")
+    };
+    if !looks_like_json {
+        return Err(PingdomError::Parse(describe("expected a JSON response".to_string())));
+    }
+    serde_json::from_slice(body).map_err(|e| PingdomError::Parse(describe(format!("invalid JSON ({})", e))))
+}
+
+impl PingdomApi {
+    pub fn new(api_key: &str, pingdom_uri: &str, options: &ClientOptions) -> Result<Self, PingdomError> {
+        let auth_value = if options.auth_scheme.is_empty() {
+            api_key.to_string()
+        } else {
+            format!("{} {}", options.auth_scheme, api_key)
+        };
+        let mut auth_value = header::HeaderValue::from_str(&auth_value)
+            .map_err(|_| PingdomError::Config("the API key contains characters that cannot be sent in a header".to_string()))?;
+        auth_value.set_sensitive(true);
+        let auth_header = header::HeaderName::from_bytes(options.auth_header_name.as_bytes())
+            .map_err(|_| PingdomError::Config(format!("invalid --auth-header-name '{}'", options.auth_header_name)))?;
+
+        // Ask for JSON explicitly, so a gateway that negotiates on Accept does not pick another type
+        let mut headers = header::HeaderMap::new();
+        headers.insert(auth_header, auth_value);
+        headers.insert(header::ACCEPT, header::HeaderValue::from_static("application/json"));
+
+        // The client advertises Accept-Encoding and decodes responses for each enabled encoding
+        let mut builder = Client::builder()
+            .default_headers(headers)
+            .gzip(options.compression)
+            .deflate(options.compression)
+            .brotli(options.compression)
+            .pool_idle_timeout(options.pool_idle_timeout)
+            .danger_accept_invalid_certs(options.accept_invalid_certs);
+        if let Some(max_idle) = options.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(path) = &options.ca_bundle {
+            for certificate in load_ca_bundle(path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        let client = builder.build()?;
+
+        Ok(PingdomApi {
+            pingdom_uri: pingdom_uri.to_string(),
+            client,
+            verbose: options.verbose,
+            checks_page_size: options.checks_page_size,
+            checks_params: options.checks_params.clone(),
+            max_payload_bytes: options.max_payload_bytes,
+            archive: options.archive.clone(),
+            account: options.account.clone(),
+            counters: Arc::default(),
+            server_clock: Arc::default(),
+            retry_budget: options.retry_budget.clone(),
+            circuit_breaker: options.circuit_breaker.clone(),
+            quota: Arc::default(),
+        })
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn stats(&self) -> RequestStats {
+        RequestStats {
+            requests: self.counters.requests.load(Ordering::Relaxed),
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            rate_limited: self.counters.rate_limited.load(Ordering::Relaxed),
+            throttled: self.counters.throttled.load(Ordering::Relaxed),
+            check_retries: self.counters.check_retries.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Pingdom's current time as of the most recent response, if it sent a `Date` header.
+    pub fn server_time(&self) -> Option<DateTime<Utc>> {
+        match self.server_clock.load(Ordering::Relaxed) {
+            0 => None,
+            secs => DateTime::from_timestamp(secs, 0),
+        }
+    }
+
+    /// What is left of the key's request quota, as of the latest response.
+    pub fn quota(&self) -> Quota {
+        *self.quota.lock().unwrap()
+    }
+
+    fn record_quota(&self, headers: &header::HeaderMap) {
+        let window = |name: &str| {
+            let (remaining, reset_secs) = parse_req_limit(headers.get(name)?.to_str().ok()?)?;
+            Some(QuotaWindow { remaining, resets_at: Instant::now() + Duration::from_secs(reset_secs) })
+        };
+        let mut quota = self.quota.lock().unwrap();
+        // Responses without the headers (a proxy's error page, another API) leave the last known quota
+        if let Some(short) = window("req-limit-short") {
+            quota.short = Some(short);
+        }
+        if let Some(long) = window("req-limit-long") {
+            quota.long = Some(long);
+        }
+    }
+
+    /// Holds a request back as the quota runs low: spread out over the time left until the
+    /// window resets when fewer than `QUOTA_SLOWDOWN_BELOW` requests are left, or until the
+    /// reset itself once none are. Each request counts against the known quota until the next
+    /// response says how much is really left, so concurrent checks do not all see the same
+    /// number. Without `Req-Limit-*` headers, nothing is delayed.
+    async fn throttle(&self) {
+        let (delay, reason) = {
+            let mut guard = self.quota.lock().unwrap();
+            let quota = &mut *guard;
+            let now = Instant::now();
+            let mut delay = Duration::ZERO;
+            let mut reason = None;
+            let mut pause_until = None;
+            for (label, slot) in [("short-term", &mut quota.short), ("long-term", &mut quota.long)] {
+                let Some(window) = slot.as_mut() else {
+                    continue;
+                };
+                if window.resets_at <= now {
+                    // Reset since; the next response tells what the new window holds
+                    *slot = None;
+                    continue;
+                }
+                let until_reset = window.resets_at - now;
+                let wait = match window.remaining {
+                    0 if until_reset <= QUOTA_MAX_PAUSE => {
+                        pause_until = Some(window.resets_at);
+                        until_reset
+                    }
+                    0 => Duration::ZERO,
+                    remaining if remaining < QUOTA_SLOWDOWN_BELOW => (until_reset / (remaining as u32 + 1)).min(QUOTA_MAX_DELAY),
+                    _ => Duration::ZERO,
+                };
+                if wait > delay {
+                    delay = wait;
+                    reason = Some(format!("{} {} requests left", window.remaining, label));
+                }
+                window.remaining = window.remaining.saturating_sub(1);
+            }
+            if let Some(until) = pause_until.filter(|&until| quota.announced_pause != Some(until)) {
+                quota.announced_pause = Some(until);
+                eprintln!(
+                    "Warning: Pingdom's request quota is used up, pausing {}s until it resets",
+                    until.saturating_duration_since(now).as_secs_f64().ceil()
+                );
+            }
+            (delay, reason)
+        };
+        if delay.is_zero() {
+            return;
+        }
+        self.counters.throttled.fetch_add(1, Ordering::Relaxed);
+        if self.verbose {
+            eprintln!("Throttling: {}, waiting {:.1}s", reason.unwrap_or_default(), delay.as_secs_f64());
+        }
+        sleep(delay).await;
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.circuit_breaker.as_deref()
+    }
+
+    pub fn retry_budget(&self) -> Option<&RetryBudget> {
+        self.retry_budget.as_deref()
+    }
+
+    /// Whether one more retry may be made: always, unless the `--retry-budget` is spent.
+    pub fn take_retry(&self) -> bool {
+        self.retry_budget.as_ref().is_none_or(|budget| budget.take())
+    }
+
+    pub fn record_check_retry(&self) {
+        self.counters.check_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Every request goes through here. A 429 is retried after the `Retry-After` delay (or an
+    /// exponential backoff when the header is missing), up to `RATE_LIMIT_RETRIES` times while
+    /// the `--retry-budget` lasts. Each attempt is first held back while the quota runs low, and
+    /// passes the circuit breaker, if there is one.
+    async fn get_json(&self, url: &str) -> Result<Value, PingdomError> {
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+            if self.verbose {
+                eprintln!("GET {}", redact_url(url));
+            }
+
+            // A request the open breaker turns away is never sent, so it is not counted
+            let admission = self.circuit_breaker.as_ref().map(|breaker| breaker.admit()).transpose()?;
+            self.counters.requests.fetch_add(1, Ordering::Relaxed);
+            let response = self.client.get(url).send().await;
+            if let (Some(breaker), Some(admission)) = (&self.circuit_breaker, admission) {
+                breaker.record(admission, breaker_failure(&response).as_deref());
+            }
+            let response = response?;
+            self.record_quota(response.headers());
+
+            if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+                if attempt < RATE_LIMIT_RETRIES && self.take_retry() {
+                    let delay = response
+                        .headers()
+                        .get(header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(1u64 << attempt);
+                    attempt += 1;
+                    self.counters.retries.fetch_add(1, Ordering::Relaxed);
+                    sleep(Duration::from_secs(delay)).await;
+                    continue;
+                }
+            }
+
+            let server_time = response
+                .headers()
+                .get(header::DATE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+            if let Some(server_time) = server_time {
+                self.server_clock.fetch_max(server_time.timestamp(), Ordering::Relaxed);
+            }
+
+            let status = response.status();
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let body = self.read_body(url, response).await?;
+            if let Some(archive) = &self.archive {
+                archive.record(self.account.as_deref(), &redact_url(url), status.as_u16(), &body).await?;
+            }
+            let json = parse_json_body(url, status, &content_type, &body);
+            if !status.is_success() {
+                // Pingdom explains errors in a JSON error object; a proxy's HTML page is
+                // described by the parse error instead
+                return Err(match json {
+                    Ok(json) => PingdomError::from_status(status, Some(&json), attempt, "no error message"),
+                    Err(e) => e,
+                });
+            }
+            return json;
+        }
+    }
+
+    /// Reads the (decoded) body chunk by chunk, giving up as soon as it grows past
+    /// `max_payload_bytes` instead of holding an arbitrarily large response in memory.
+    async fn read_body(&self, url: &str, mut response: reqwest::Response) -> Result<Vec<u8>, PingdomError> {
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > self.max_payload_bytes {
+                return Err(PingdomError::PayloadTooLarge {
+                    url: redact_url(url),
+                    limit: self.max_payload_bytes,
+                });
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// Lists every check, `checks_page_size` at a time, and returns them as one `/checks`
+    /// response. A response without a `checks` array is returned as it is.
+    pub async fn get_checks(&self) -> Result<Value, PingdomError> {
+        let limit = self.checks_page_size;
+        let mut checks = Vec::new();
+        loop {
+            let mut url = format!("{}/checks?include_tags=true&limit={}&offset={}", self.pingdom_uri, limit, checks.len());
+            for (key, value) in &self.checks_params {
+                url = format!("{}&{}={}", url, key, query_escape(value));
+            }
+            let mut page = self.get_json(&url).await?;
+            let Some(page_checks) = page["checks"].as_array_mut() else {
+                return Ok(page);
+            };
+            let fetched = page_checks.len();
+            checks.append(page_checks);
+            let total = page["counts"]["total"].as_u64();
+            if fetched < limit || total.is_some_and(|t| checks.len() as u64 >= t) {
+                page["checks"] = Value::Array(checks);
+                return Ok(page);
+            }
+        }
+    }
+
+    pub async fn get_check(&self, check_id: u64) -> Result<Value, PingdomError> {
+        self.get_json(&format!("{}/checks/{}", self.pingdom_uri, check_id)).await
+    }
+
+    pub async fn get_probes(&self) -> Result<Value, PingdomError> {
+        self.get_json(&format!("{}/probes", self.pingdom_uri)).await
+    }
+
+    pub async fn get_check_probes(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, PingdomError> {
+        let url = format!(
+            "{}/summary.probes/{}?from={}&to={}",
+            self.pingdom_uri, check_id, from, to
+        );
+
+        self.get_json(&url).await
+    }
+
+    pub async fn get_perf_summary(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+        includeuptime: &str,
+        resolution: &str,
+        probes: Option<&str>,
+    ) -> Result<Value, PingdomError> {
+        let mut url = format!(
+            "{}/summary.performance/{}?from={}&to={}&includeuptime={}&resolution={}",
+            self.pingdom_uri, check_id, from, to, includeuptime, resolution
+        );
+        if let Some(probes) = probes {
+            url.push_str(&format!("&probes={}", probes));
+        }
+
+        self.get_json(&url).await
+    }
+
+    pub async fn get_outage_summary(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+    ) -> Result<Value, PingdomError> {
+        let url = format!(
+            "{}/summary.outage/{}?from={}&to={}",
+            self.pingdom_uri, check_id, from, to
+        );
+
+        self.get_json(&url).await
+    }
+
+    pub async fn calculate_uptime(
+        &self,
+        check_id: u64,
+        check_name: &str,
+        from: &str,
+        to: &str,
+        options: &UptimeOptions,
+    ) -> Result<UptimeResult, PingdomError> {
+        let probes = options.probes.as_deref();
+        let include_uptime = if options.include_uptime { "true" } else { "false" };
+        // Both summaries are independent, so fetch them side by side rather than back to back.
+        // Maintenance windows need the outages to tell when the downtime happened, and
+        // --exclude-unconfirmed to tell how much of it was confirmed.
+        let needs_outages = options.with_outages || options.exclude_unconfirmed || !options.maintenance.is_empty();
+        let (perf_summary, outage_summary) = if needs_outages {
+            let (perf, outage) = tokio::try_join!(
+                self.get_perf_summary(check_id, from, to, include_uptime, options.resolution.param(), probes),
+                self.get_outage_summary(check_id, from, to),
+            )?;
+            (perf, Some(outage))
+        } else {
+            (self.get_perf_summary(check_id, from, to, include_uptime, options.resolution.param(), probes).await?, None)
+        };
+
+        let check_uptime = perf_summary;
+        if check_uptime["error"].is_object() {
+            // An error object in place of the summary, even though the status said success
+            let status = check_uptime["error"]["statuscode"]
+                .as_u64()
+                .and_then(|code| reqwest::StatusCode::from_u16(code as u16).ok())
+                .unwrap_or(reqwest::StatusCode::OK);
+            return Err(PingdomError::from_status(status, Some(&check_uptime), 0, "no error message"));
+        }
+
+        // Some check types send the buckets without the `summary` wrapper, or buckets without
+        // some of the totals; whatever is missing counts as 0 and the result is flagged
+        let key = options.resolution.buckets_key();
+        let summary = if check_uptime["summary"].is_object() { &check_uptime["summary"] } else { &check_uptime };
+        let buckets: &[Value] = summary[key].as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut incomplete_data = !check_uptime["summary"].is_object() || !summary[key].is_array();
+        let (mut uptime, mut downtime, mut unmonitored) = (0, 0, 0);
+        let mut breakdown = Vec::new();
+        for u in buckets {
+            let totals = (u["uptime"].as_u64(), u["downtime"].as_u64(), u["unmonitored"].as_u64());
+            if options.include_uptime && (totals.0.is_none() || totals.1.is_none() || totals.2.is_none()) {
+                incomplete_data = true;
+            }
+            let (bucket_uptime, bucket_downtime, bucket_unmonitored) = (totals.0.unwrap_or(0), totals.1.unwrap_or(0), totals.2.unwrap_or(0));
+            uptime += bucket_uptime;
+            downtime += bucket_downtime;
+            unmonitored += bucket_unmonitored;
+            if let Some(start) = u["starttime"].as_i64().and_then(|t| DateTime::from_timestamp(t, 0)).filter(|_| options.breakdown) {
+                let bucket_max = bucket_uptime + bucket_downtime + bucket_unmonitored;
+                breakdown.push(BucketUptime {
+                    start,
+                    uptime: bucket_uptime,
+                    downtime: bucket_downtime,
+                    unmonitored: bucket_unmonitored,
+                    percentage: uptime_percentage(bucket_uptime, bucket_unmonitored, bucket_max),
+                    anomaly: None,
+                });
+            }
+        }
+
+        let responses: Vec<u64> = buckets.iter().filter_map(|u| u["avgresponse"].as_u64()).collect();
+        let avg_response_ms = (!responses.is_empty()).then(|| responses.iter().sum::<u64>() / responses.len() as u64);
+
+        let mut max_uptime = uptime + downtime + unmonitored;
+
+        // Pingdom only enters the down state once a failed test has been confirmed, so downtime
+        // outside the down states of the outage summary counts as uptime. Without any states
+        // the total downtime is kept and `unconfirmed_downtime` stays unset.
+        let unconfirmed_downtime = outage_summary
+            .as_ref()
+            .filter(|check_outages| options.exclude_unconfirmed && check_outages["summary"]["states"].is_array())
+            .map(|check_outages| {
+                let window = (from.parse().unwrap_or(i64::MIN), to.parse().unwrap_or(i64::MAX));
+                let confirmed = maintenance::downtime_within(check_outages, &[window]);
+                let unconfirmed = downtime.saturating_sub(confirmed);
+                downtime -= unconfirmed;
+                uptime += unconfirmed;
+                unconfirmed
+            });
+
+        // Downtime during maintenance is left out of the window altogether, so it counts
+        // neither as down nor as up
+        let mut raw_percentage = None;
+        let maintenance_downtime = outage_summary.as_ref().filter(|_| !options.maintenance.is_empty()).map(|check_outages| {
+            let excluded = maintenance::downtime_within(check_outages, &options.maintenance).min(downtime);
+            raw_percentage = uptime_percentage(uptime, unmonitored, max_uptime);
+            downtime -= excluded;
+            max_uptime -= excluded;
+            excluded
+        });
+
+        let down_periods: Option<Vec<u64>> = outage_summary.filter(|_| options.with_outages).map(|check_outages| {
+            check_outages["summary"]["states"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|s| s["status"] == "down")
+                .map(|s| s["timeto"].as_u64().unwrap_or(0).saturating_sub(s["timefrom"].as_u64().unwrap_or(0)))
+                .collect()
+        });
+        let outages = down_periods.as_ref().map(Vec::len);
+        let outage_distribution = down_periods.as_deref().map(OutageDistribution::from_durations);
+
+        Ok(UptimeResult {
+            id: check_id,
+            account: None,
+            name: check_name.to_string(),
+            target: None,
+            tags: Vec::new(),
+            uptime,
+            downtime,
+            unmonitored,
+            max_uptime,
+            percentage: uptime_percentage(uptime, unmonitored, max_uptime),
+            // Round once over the whole window so partial minutes from individual weeks are not lost
+            downtime_mins: options.downtime_rounding.minutes(downtime),
+            avg_response_ms,
+            outages,
+            outage_distribution,
+            breakdown: options.breakdown.then_some(breakdown),
+            regions: None,
+            sla: None,
+            sla_met: None,
+            error_budget_mins: None,
+            error_budget_remaining_mins: None,
+            error_budget_exhausted: None,
+            downtime_cost: None,
+            last_down: None,
+            last_up: None,
+            last_test: None,
+            created: None,
+            created_in_window: false,
+            incomplete_data,
+            low_confidence: None,
+            maintenance_downtime,
+            raw_percentage,
+            unconfirmed_downtime,
+            integrations: None,
+            alert_contacts: None,
+            alerting: None,
+            response_trend: None,
+            series: None,
+        })
+    }
+
+    /// Fetches the day-resolution performance summary on its own and returns the average
+    /// response time of each day, dated in `tz`. Days without a measurement are left out.
+    pub async fn response_trend(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+        tz: FixedOffset,
+    ) -> Result<Vec<TrendPoint>, PingdomError> {
+        let summary = self.get_perf_summary(check_id, from, to, "false", Resolution::Day.param(), None).await?;
+        Ok(summary["summary"][Resolution::Day.buckets_key()]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|day| {
+                Some(TrendPoint {
+                    date: dates::from_epoch(day["starttime"].as_i64(), tz)?.date_naive(),
+                    avg_response_ms: day["avgresponse"].as_u64()?,
+                })
+            })
+            .collect())
+    }
+
+    /// The check's availability per `period`, summed from its day-resolution performance
+    /// summary. Days are assigned to periods by their start date in `tz`; periods without any
+    /// day of data are left out.
+    pub async fn period_series(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+        period: Period,
+        tz: FixedOffset,
+        rounding: DowntimeRounding,
+    ) -> Result<Vec<PeriodUptime>, PingdomError> {
+        let summary = self.get_perf_summary(check_id, from, to, "true", Resolution::Day.param(), None).await?;
+        let mut periods: BTreeMap<NaiveDate, (u64, u64, u64)> = BTreeMap::new();
+        for day in summary["summary"][Resolution::Day.buckets_key()].as_array().into_iter().flatten() {
+            let Some(date) = dates::from_epoch(day["starttime"].as_i64(), tz) else {
+                continue;
+            };
+            let totals = periods.entry(period.floor(date.date_naive())).or_default();
+            totals.0 += day["uptime"].as_u64().unwrap_or(0);
+            totals.1 += day["downtime"].as_u64().unwrap_or(0);
+            totals.2 += day["unmonitored"].as_u64().unwrap_or(0);
+        }
+        Ok(periods
+            .into_iter()
+            .map(|(start, (uptime, downtime, unmonitored))| PeriodUptime {
+                start,
+                uptime,
+                downtime,
+                unmonitored,
+                percentage: uptime_percentage(uptime, unmonitored, uptime + downtime + unmonitored),
+                downtime_mins: rounding.minutes(downtime),
+            })
+            .collect())
+    }
+
+    /// Fills in the alerting fields from the check's detail: integrations (webhooks, Slack, ...)
+    /// and user/team contacts that Pingdom notifies when the check goes down. The detail also
+    /// refines the check's `target`.
+    pub async fn fetch_alerting(&self, result: &mut UptimeResult) -> Result<(), PingdomError> {
+        let detail = self.get_check(result.id).await?;
+        let check = &detail["check"];
+        let count = |key: &str| check[key].as_array().map(Vec::len).unwrap_or(0);
+
+        let integrations = count("integrationids");
+        let alert_contacts = count("userids") + count("teamids");
+        result.integrations = Some(integrations);
+        result.alert_contacts = Some(alert_contacts);
+        result.alerting = Some(integrations + alert_contacts > 0);
+        // The detail knows more about the target than the checks list did
+        if let Some(target) = check_target(check) {
+            result.target = Some(target);
+        }
+
+        Ok(())
+    }
+
+    /// Groups the probes that actually tested the check during the window by their region.
+    pub async fn probes_by_region<'a>(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+        probe_regions: &'a HashMap<u64, String>,
+    ) -> Result<HashMap<&'a str, Vec<String>>, PingdomError> {
+        let check_probes = self.get_check_probes(check_id, from, to).await?;
+
+        let mut probes_by_region: HashMap<&str, Vec<String>> = HashMap::new();
+        for probe_id in check_probes["probes"].as_array().into_iter().flatten().filter_map(Value::as_u64) {
+            if let Some(region) = probe_regions.get(&probe_id) {
+                probes_by_region.entry(region).or_default().push(probe_id.to_string());
+            }
+        }
+
+        Ok(probes_by_region)
+    }
+
+    /// Computes availability per probe region, using only the probes that actually tested the
+    /// check during the window. Returns an empty map when the check exposes no probe data.
+    pub async fn calculate_region_uptime(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+        resolution: Resolution,
+        probe_regions: &HashMap<u64, String>,
+    ) -> Result<BTreeMap<String, f64>, PingdomError> {
+        let probes_by_region = self.probes_by_region(check_id, from, to, probe_regions).await?;
+
+        let region_summaries = futures::future::join_all(probes_by_region.into_iter().map(|(region, probes)| async move {
+            let summary = self.get_perf_summary(check_id, from, to, "true", resolution.param(), Some(&probes.join(","))).await;
+            (region, summary)
+        }))
+        .await;
+
+        let mut regions = BTreeMap::new();
+        for (region, summary) in region_summaries {
+            let region_uptime = summary?;
+            let (mut uptime, mut downtime, mut unmonitored) = (0, 0, 0);
+            for u in region_uptime["summary"][resolution.buckets_key()].as_array().into_iter().flatten() {
+                uptime += u["uptime"].as_u64().unwrap_or(0);
+                downtime += u["downtime"].as_u64().unwrap_or(0);
+                unmonitored += u["unmonitored"].as_u64().unwrap_or(0);
+            }
+            if let Some(percentage) = uptime_percentage(uptime, unmonitored, uptime + downtime + unmonitored) {
+                regions.insert(region.to_string(), percentage);
+            }
+        }
+
+        Ok(regions)
+    }
+
+    /// Uptime totals for each probe that tested the check during the window, one summary request
+    /// per probe, most downtime first. Empty when the check exposes no probe data.
+    pub async fn calculate_probe_uptime(
+        &self,
+        check_id: u64,
+        from: &str,
+        to: &str,
+        resolution: Resolution,
+        downtime_rounding: DowntimeRounding,
+    ) -> Result<Vec<ProbeUptime>, PingdomError> {
+        let check_probes = self.get_check_probes(check_id, from, to).await?;
+        let probe_ids: Vec<u64> = check_probes["probes"].as_array().into_iter().flatten().filter_map(Value::as_u64).collect();
+
+        let mut probes = stream::iter(probe_ids)
+            .map(|probe_id| async move {
+                let summary = self.get_perf_summary(check_id, from, to, "true", resolution.param(), Some(&probe_id.to_string())).await?;
+                let (mut uptime, mut downtime, mut unmonitored) = (0, 0, 0);
+                for u in summary["summary"][resolution.buckets_key()].as_array().into_iter().flatten() {
+                    uptime += u["uptime"].as_u64().unwrap_or(0);
+                    downtime += u["downtime"].as_u64().unwrap_or(0);
+                    unmonitored += u["unmonitored"].as_u64().unwrap_or(0);
+                }
+                Ok::<_, PingdomError>(ProbeUptime {
+                    probe_id,
+                    name: None,
+                    region: None,
+                    uptime,
+                    downtime,
+                    unmonitored,
+                    percentage: uptime_percentage(uptime, unmonitored, uptime + downtime + unmonitored),
+                    downtime_mins: downtime_rounding.minutes(downtime),
+                })
+            })
+            .buffer_unordered(PROBE_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        probes.sort_by(|a, b| b.downtime.cmp(&a.downtime).then(a.probe_id.cmp(&b.probe_id)));
+
+        Ok(probes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use axum::{response::IntoResponse, routing::get, Json, Router};
+    use serde_json::json;
+    use tokio::sync::Barrier;
+    use tokio::time::timeout;
+
+    fn uptime_options() -> UptimeOptions {
+        UptimeOptions {
+            with_outages: false,
+            downtime_rounding: DowntimeRounding::Floor,
+            resolution: Resolution::Week,
+            include_uptime: true,
+            probes: None,
+            breakdown: false,
+            maintenance: Vec::new(),
+            exclude_unconfirmed: false,
+        }
+    }
+
+    /// Calculates check 1 over one hour against a server answering `summary` as its performance summary.
+    async fn calculate_with_summary(summary: Value, options: &UptimeOptions) -> UptimeResult {
+        let app = Router::new().route("/summary.performance/{id}", get(move || async move { Json(summary) }));
+        let api = testing::client(&testing::serve(app).await);
+        api.calculate_uptime(1, "web", "0", "3600", options).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn outage_summary_is_requested_alongside_the_performance_summary() {
+        // Each summary is only answered once the other one has been asked for too, so the
+        // calculation can only finish when both requests are in flight at the same time
+        let barrier = Arc::new(Barrier::new(2));
+        let (perf_barrier, outage_barrier) = (barrier.clone(), barrier);
+        let app = Router::new()
+            .route(
+                "/summary.performance/{id}",
+                get(move || async move {
+                    perf_barrier.wait().await;
+                    Json(json!({"summary": {"weeks": [{"starttime": 0, "uptime": 3000, "downtime": 600, "unmonitored": 0}]}}))
+                }),
+            )
+            .route(
+                "/summary.outage/{id}",
+                get(move || async move {
+                    outage_barrier.wait().await;
+                    Json(json!({"summary": {"states": [
+                        {"status": "up", "timefrom": 0, "timeto": 3000},
+                        {"status": "down", "timefrom": 3000, "timeto": 3600}
+                    ]}}))
+                }),
+            );
+        let api = testing::client(&testing::serve(app).await);
+
+        let options = UptimeOptions { with_outages: true, ..uptime_options() };
+        let result = timeout(Duration::from_secs(5), api.calculate_uptime(1, "web", "0", "3600", &options))
+            .await
+            .expect("the summaries were requested one after the other")
+            .unwrap();
+
+        assert_eq!(api.stats().requests, 2);
+        assert_eq!(result.outages, Some(1));
+        assert_eq!(result.downtime, 600);
+    }
+
+    #[tokio::test]
+    async fn outage_summary_is_not_requested_without_outages() {
+        let app = Router::new().route(
+            "/summary.performance/{id}",
+            get(|| async { Json(json!({"summary": {"weeks": [{"starttime": 0, "uptime": 3600, "downtime": 0, "unmonitored": 0}]}})) }),
+        );
+        let api = testing::client(&testing::serve(app).await);
+
+        let result = api.calculate_uptime(1, "web", "0", "3600", &uptime_options()).await.unwrap();
+
+        assert_eq!(api.stats().requests, 1);
+        assert_eq!(result.outages, None);
+    }
+
+    /// Calculates check 1 over one hour with `outage` as its outage summary, against a
+    /// performance summary of 3000s up and 600s down.
+    async fn calculate_with_outages(outage: Value) -> UptimeResult {
+        let app = Router::new()
+            .route(
+                "/summary.performance/{id}",
+                get(|| async { Json(json!({"summary": {"weeks": [{"starttime": 0, "uptime": 3000, "downtime": 600, "unmonitored": 0}]}})) }),
+            )
+            .route("/summary.outage/{id}", get(move || async move { Json(outage) }));
+        let api = testing::client(&testing::serve(app).await);
+        let options = UptimeOptions { exclude_unconfirmed: true, ..uptime_options() };
+        api.calculate_uptime(1, "web", "0", "3600", &options).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn downtime_outside_the_down_states_counts_as_uptime() {
+        // Only the last 300s were confirmed, and the down state runs past the end of the window
+        let result = calculate_with_outages(json!({"summary": {"states": [
+            {"status": "up", "timefrom": 0, "timeto": 3000},
+            {"status": "unknown", "timefrom": 3000, "timeto": 3300},
+            {"status": "down", "timefrom": 3300, "timeto": 4000}
+        ]}}))
+        .await;
+
+        assert_eq!(result.unconfirmed_downtime, Some(300));
+        assert_eq!((result.uptime, result.downtime, result.max_uptime), (3300, 300, 3600));
+        assert_eq!(result.percentage, Some(91.6667));
+    }
+
+    #[tokio::test]
+    async fn total_downtime_is_kept_without_outage_states() {
+        let result = calculate_with_outages(json!({"summary": {}})).await;
+
+        assert_eq!(result.unconfirmed_downtime, None);
+        assert_eq!((result.uptime, result.downtime), (3000, 600));
+    }
+
+    #[tokio::test]
+    async fn html_error_page_is_reported_with_status_and_body() {
+        let page = "<html><body><h1>502 Bad Gateway</h1></body></html>";
+        let app = Router::new().route(
+            "/checks/{id}",
+            get(move || async move {
+                (axum::http::StatusCode::BAD_GATEWAY, [(header::CONTENT_TYPE, "text/html")], page)
+            }),
+        );
+        let api = testing::client(&testing::serve(app).await);
+
+        let error = api.get_check(1).await.unwrap_err();
+
+        assert!(matches!(error, PingdomError::Parse(_)), "{:?}", error);
+        let message = error.to_string();
+        assert!(message.starts_with("expected a JSON response from http://"), "{}", message);
+        assert!(message.contains("(HTTP 502, content type 'text/html'): <html><body><h1>502 Bad Gateway"), "{}", message);
+    }
+
+    #[test]
+    fn truncated_json_body_is_reported_with_its_start() {
+        let body = br#"{"checks": [{"id": 1, "name": "web"#;
+
+        let message = parse_json_body("https://api.example.com/checks", reqwest::StatusCode::OK, "application/json", body)
+            .unwrap_err()
+            .to_string();
+
+        assert!(message.starts_with("invalid JSON (EOF while parsing"), "{}", message);
+        assert!(message.ends_with(r#"(HTTP 200, content type 'application/json'): {"checks": [{"id": 1, "name": "web"#), "{}", message);
+    }
+
+    #[test]
+    fn long_and_non_utf8_bodies_are_quoted_in_part() {
+        let mut body = vec![0xff, 0xfe];
+        body.extend(std::iter::repeat_n(b'x', 500));
+
+        let message = parse_json_body("https://api.example.com/checks", reqwest::StatusCode::OK, "", &body)
+            .unwrap_err()
+            .to_string();
+
+        let snippet = message.split_once("''): ").map(|(_, snippet)| snippet).unwrap();
+        assert!(snippet.starts_with("\u{fffd}\u{fffd}x"), "{}", snippet);
+        assert!(snippet.ends_with("x..."), "{}", snippet);
+        assert_eq!(snippet.chars().count(), BODY_SNIPPET_LEN + 3);
+    }
+
+    #[tokio::test]
+    async fn json_is_asked_for_from_a_server_that_negotiates_on_accept() {
+        let app = Router::new().route(
+            "/checks/{id}",
+            get(|headers: header::HeaderMap| async move {
+                let wants_json = headers.get(header::ACCEPT).is_some_and(|accept| accept == "application/json");
+                if wants_json {
+                    Json(json!({"check": {"id": 1, "name": "web"}})).into_response()
+                } else {
+                    ([(header::CONTENT_TYPE, "text/html")], "<html><body>web</body></html>").into_response()
+                }
+            }),
+        );
+        let url = testing::serve(app).await;
+
+        // Without the header the server picks HTML
+        let html = reqwest::get(format!("{}/checks/1", url)).await.unwrap();
+        assert_eq!(html.headers()[header::CONTENT_TYPE], "text/html");
+
+        let check = testing::client(&url).get_check(1).await.unwrap();
+        assert_eq!(check["check"]["name"], "web");
+    }
+
+    #[tokio::test]
+    async fn requests_turned_away_by_the_open_breaker_are_not_counted() {
+        let app = Router::new().route("/checks/{id}", get(|| async { axum::http::StatusCode::SERVICE_UNAVAILABLE }));
+        let breaker = Arc::new(CircuitBreaker::new(50.0, 1, Duration::from_secs(60)));
+        let options = ClientOptions { circuit_breaker: Some(breaker), ..ClientOptions::default() };
+        let api = PingdomApi::new("test-key", &testing::serve(app).await, &options).unwrap();
+
+        // The first failure trips the breaker, so the second request is never sent
+        assert!(api.get_check(1).await.is_err());
+        let error = api.get_check(1).await.unwrap_err();
+
+        assert!(matches!(error, PingdomError::CircuitOpen(_)), "{:?}", error);
+        assert_eq!(api.stats().requests, 1);
+    }
+
+    #[test]
+    fn non_json_content_type_is_not_parsed() {
+        let error = parse_json_body("https://api.example.com/checks", reqwest::StatusCode::OK, "text/plain", br#"{"checks": []}"#)
+            .unwrap_err();
+
+        assert!(matches!(error, PingdomError::Parse(_)), "{:?}", error);
+        assert!(error.to_string().starts_with("expected a JSON response from https://api.example.com/checks"), "{}", error);
+    }
+
+    #[test]
+    fn body_without_content_type_is_parsed_when_it_looks_like_json() {
+        let parse = |body: &[u8]| parse_json_body("https://api.example.com/checks", reqwest::StatusCode::OK, "", body);
+
+        assert_eq!(parse(b" \n{\"checks\": []}").unwrap(), json!({"checks": []}));
+        assert!(parse(b"<html></html>").unwrap_err().to_string().starts_with("expected a JSON response"));
+    }
+
+    #[tokio::test]
+    async fn complete_buckets_are_not_flagged() {
+        let summary = json!({"summary": {"weeks": [
+            {"starttime": 0, "uptime": 3000, "downtime": 600, "unmonitored": 0}
+        ]}});
+
+        let result = calculate_with_summary(summary, &uptime_options()).await;
+
+        assert!(!result.incomplete_data);
+        assert_eq!((result.uptime, result.downtime, result.max_uptime), (3000, 600, 3600));
+    }
+
+    #[tokio::test]
+    async fn missing_bucket_totals_count_as_zero_and_are_flagged() {
+        let summary = json!({"summary": {"weeks": [
+            {"starttime": 0, "uptime": 1800, "unmonitored": 0},
+            {"starttime": 1800, "downtime": 600, "unmonitored": 0},
+            {"starttime": 3600, "uptime": 1200, "downtime": 0}
+        ]}});
+
+        let result = calculate_with_summary(summary, &uptime_options()).await;
+
+        assert!(result.incomplete_data);
+        assert_eq!((result.uptime, result.downtime, result.unmonitored), (3000, 600, 0));
+        assert_eq!(result.percentage, Some(83.3333));
+    }
+
+    #[tokio::test]
+    async fn buckets_without_the_summary_wrapper_are_read_and_flagged() {
+        let summary = json!({"weeks": [{"starttime": 0, "uptime": 3600, "downtime": 0, "unmonitored": 0}]});
+
+        let result = calculate_with_summary(summary, &uptime_options()).await;
+
+        assert!(result.incomplete_data);
+        assert_eq!(result.uptime, 3600);
+        assert_eq!(result.percentage, Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn summary_without_buckets_is_flagged_as_unmonitored() {
+        let result = calculate_with_summary(json!({"summary": {}}), &uptime_options()).await;
+
+        assert!(result.incomplete_data);
+        assert_eq!(result.max_uptime, 0);
+        assert_eq!(result.percentage, None);
+    }
+
+    #[tokio::test]
+    async fn missing_totals_are_expected_without_includeuptime() {
+        let summary = json!({"summary": {"weeks": [{"starttime": 0, "avgresponse": 250}]}});
+        let options = UptimeOptions { include_uptime: false, ..uptime_options() };
+
+        let result = calculate_with_summary(summary, &options).await;
+
+        assert!(!result.incomplete_data);
+        assert_eq!(result.avg_response_ms, Some(250));
+    }
+
+    #[test]
+    fn target_of_a_listed_check_is_its_hostname() {
+        assert_eq!(check_target(&json!({"type": "tcp", "hostname": "db.example.com"})).as_deref(), Some("db.example.com"));
+        assert_eq!(check_target(&json!({"type": "http", "hostname": ""})), None);
+    }
+
+    #[test]
+    fn target_of_an_http_check_is_its_url() {
+        let plain = json!({"hostname": "example.com", "type": {"http": {"url": "/health", "encryption": false, "port": 80}}});
+        let tls = json!({"hostname": "example.com", "type": {"http": {"url": "/", "encryption": true, "port": 8443}}});
+
+        assert_eq!(check_target(&plain).as_deref(), Some("http://example.com/health"));
+        assert_eq!(check_target(&tls).as_deref(), Some("https://example.com:8443/"));
+    }
+
+    #[test]
+    fn target_of_a_tcp_check_has_its_port() {
+        let listed_host = json!({"hostname": "db.example.com", "type": {"tcp": {"port": 5432}}});
+        let detail_host = json!({"hostname": "example.com", "type": {"tcp": {"host": "10.0.0.5", "port": 5432}}});
+
+        assert_eq!(check_target(&listed_host).as_deref(), Some("db.example.com:5432"));
+        assert_eq!(check_target(&detail_host).as_deref(), Some("10.0.0.5:5432"));
+    }
+
+    #[test]
+    fn target_of_a_ping_check_is_its_host() {
+        let check = json!({"hostname": "example.com", "type": {"ping": {"host": "192.0.2.1"}}});
+
+        assert_eq!(check_target(&check).as_deref(), Some("192.0.2.1"));
+        assert_eq!(check_target(&json!({"hostname": "example.com", "type": {"ping": {}}})).as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn target_of_a_dns_check_names_its_nameserver() {
+        let check = json!({"hostname": "example.com", "type": {"dns": {"nameserver": "ns1.example.net", "expectedip": "192.0.2.1"}}});
+
+        assert_eq!(check_target(&check).as_deref(), Some("example.com@ns1.example.net"));
+        assert_eq!(check_target(&json!({"hostname": "example.com", "type": {"dns": {}}})).as_deref(), Some("example.com"));
+    }
+}
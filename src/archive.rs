@@ -0,0 +1,87 @@
+//! `--archive-dir`: keeps every raw API response of a run on disk, so a report can later be
+//! traced back to exactly what Pingdom answered.
+
+use chrono::Utc;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::error::PingdomError;
+
+/// One run's directory under `--archive-dir`. Each response body is written verbatim to its own
+/// numbered file and listed in `index.tsv` with the time, status, account and URL it came from.
+/// Request headers, and with them the API key, are never written.
+#[derive(Debug)]
+pub struct ResponseArchive {
+    dir: PathBuf,
+    next: AtomicU64,
+}
+
+impl ResponseArchive {
+    /// Creates `<root>/<UTC timestamp>/` for this run, with a `-2`, `-3`, ... suffix when a run
+    /// in the same second already took the name.
+    pub fn create(root: &Path) -> Result<Self, Box<dyn Error>> {
+        std::fs::create_dir_all(root).map_err(|e| format!("could not create {}: {}", root.display(), e))?;
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        for attempt in 1.. {
+            let name = if attempt == 1 { stamp.clone() } else { format!("{}-{}", stamp, attempt) };
+            let dir = root.join(name);
+            match std::fs::create_dir(&dir) {
+                Ok(()) => return Ok(ResponseArchive { dir, next: AtomicU64::new(1) }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(format!("could not create {}: {}", dir.display(), e).into()),
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes one response body and appends it to the index. `url` must already be redacted.
+    pub async fn record(&self, account: Option<&str>, url: &str, status: u16, body: &[u8]) -> Result<(), PingdomError> {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let file_name = format!("{:06}-{}.json", seq, endpoint_slug(url));
+        let path = self.dir.join(&file_name);
+        fs::write(&path, body).await.map_err(|source| PingdomError::Io {
+            context: format!("could not archive a response to {}", path.display()),
+            source,
+        })?;
+
+        // One write per line, so lines from concurrent requests never interleave
+        let line = format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            file_name,
+            Utc::now().to_rfc3339(),
+            status,
+            account.unwrap_or("-"),
+            url
+        );
+        let index = self.dir.join("index.tsv");
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&index)
+            .await
+            .map_err(|source| PingdomError::Io { context: format!("could not open {}", index.display()), source })?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|source| PingdomError::Io { context: format!("could not write {}", index.display()), source })?;
+        Ok(())
+    }
+}
+
+/// The endpoint part of a URL as a file name, e.g. `summary.performance-1234567`.
+fn endpoint_slug(url: &str) -> String {
+    let path = reqwest::Url::parse(url).map(|u| u.path().to_string()).unwrap_or_default();
+    let slug: String = path
+        .trim_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' { c } else { '-' })
+        .collect();
+    if slug.is_empty() { "response".to_string() } else { slug }
+}
@@ -0,0 +1,107 @@
+//! `--breaker-threshold`: a circuit breaker over the requests of the whole run. When too many of
+//! the recent requests fail, no more are sent for a cooldown; then a single trial request decides
+//! whether to resume or to wait out another cooldown.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::PingdomError;
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Failure percentage of the window above which the breaker trips
+    threshold: f64,
+    /// How many of the most recent requests are considered
+    window: usize,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Whether each of the last `window` requests failed, oldest first
+    outcomes: VecDeque<bool>,
+    /// Set while the breaker is open: until when, and why it tripped
+    open: Option<(Instant, String)>,
+    /// A trial request is in flight after the cooldown (half-open)
+    trial: bool,
+    trips: u64,
+}
+
+/// Whether an admitted request is the half-open trial, whose outcome closes or reopens the breaker.
+#[derive(Clone, Copy, Debug)]
+pub struct Admission {
+    trial: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: f64, window: usize, cooldown: Duration) -> Self {
+        CircuitBreaker { threshold, window, cooldown, state: Mutex::default() }
+    }
+
+    /// Lets a request through, or fails it straight away while the breaker is open. After the
+    /// cooldown the first request through is the trial; the others keep failing until it is back.
+    pub fn admit(&self) -> Result<Admission, PingdomError> {
+        let mut state = self.state.lock().unwrap();
+        let Some((until, reason)) = &state.open else {
+            return Ok(Admission { trial: false });
+        };
+        let now = Instant::now();
+        if now < *until || state.trial {
+            let wait = until.saturating_duration_since(now).as_secs();
+            return Err(PingdomError::CircuitOpen(format!(
+                "circuit breaker open ({}); no requests are sent for another {}s",
+                reason, wait
+            )));
+        }
+        state.trial = true;
+        Ok(Admission { trial: true })
+    }
+
+    /// Records the outcome of an admitted request, tripping the breaker when the failures of
+    /// the last `window` requests exceed the threshold. `error` describes a failure.
+    pub fn record(&self, admission: Admission, error: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        if admission.trial {
+            state.trial = false;
+            match error {
+                Some(error) => {
+                    eprintln!("Warning: circuit breaker trial request failed ({}), pausing for another {}s", error, self.cooldown.as_secs());
+                    state.open = Some((Instant::now() + self.cooldown, format!("the trial request failed: {}", error)));
+                }
+                None => {
+                    eprintln!("Circuit breaker closed: the trial request succeeded, resuming requests");
+                    state.open = None;
+                    state.outcomes.clear();
+                }
+            }
+            return;
+        }
+        // Requests sent before the breaker tripped say nothing new
+        if state.open.is_some() {
+            return;
+        }
+
+        state.outcomes.push_back(error.is_some());
+        if state.outcomes.len() > self.window {
+            state.outcomes.pop_front();
+        }
+        let failures = state.outcomes.iter().filter(|&&failed| failed).count();
+        let Some(error) = error else {
+            return;
+        };
+        if state.outcomes.len() == self.window && failures as f64 * 100.0 > self.threshold * self.window as f64 {
+            let reason = format!("{} of the last {} requests failed, the latest with: {}", failures, self.window, error);
+            eprintln!("Warning: circuit breaker tripped: {}; pausing requests for {}s", reason, self.cooldown.as_secs());
+            state.open = Some((Instant::now() + self.cooldown, reason));
+            state.outcomes.clear();
+            state.trips += 1;
+        }
+    }
+
+    /// How many times the breaker tripped, not counting reopening after a failed trial.
+    pub fn trips(&self) -> u64 {
+        self.state.lock().unwrap().trips
+    }
+}
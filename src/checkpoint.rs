@@ -0,0 +1,171 @@
+//! `--checkpoint`: records every finished check as one ndjson line, so a run that was
+//! interrupted or killed can be resumed without calculating those checks again.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::report::UptimeResult;
+
+/// One line of the file: a check's result and the window it was requested for, before it was
+/// cut off at Pingdom's current time.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    check: UptimeResult,
+}
+
+/// Reads the results an earlier run left in `path`, grouped by the index of their window in
+/// `windows`. A missing file is an empty checkpoint. Lines that do not parse, such as the last
+/// one of a run that was killed mid-write, are skipped with a warning; an entry for any other
+/// window means the file belongs to another report and is an error.
+pub fn load(path: &Path, windows: &[(DateTime<Utc>, DateTime<Utc>)]) -> Result<Vec<Vec<UptimeResult>>, Box<dyn Error>> {
+    let mut done: Vec<Vec<UptimeResult>> = windows.iter().map(|_| Vec::new()).collect();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(done),
+        Err(e) => return Err(format!("could not open checkpoint {}: {}", path.display(), e).into()),
+    };
+
+    let mut unreadable = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("could not read checkpoint {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(entry) = serde_json::from_str::<Entry>(&line) else {
+            unreadable += 1;
+            continue;
+        };
+        let Some(window) = windows.iter().position(|&(from, to)| (from, to) == (entry.from, entry.to)) else {
+            return Err(format!(
+                "checkpoint {} was written for the window {} to {}, not this report's; remove it or choose another file",
+                path.display(),
+                entry.from.to_rfc3339(),
+                entry.to.to_rfc3339()
+            )
+            .into());
+        };
+        done[window].push(entry.check);
+    }
+    if unreadable > 0 {
+        eprintln!("Warning: skipped {} unreadable lines of checkpoint {}", unreadable, path.display());
+    }
+    Ok(done)
+}
+
+/// Appends results to the checkpoint file. Checks finish concurrently, so every line goes
+/// through a channel to a single writer task, which writes it in one piece and flushes it
+/// before taking the next: lines never interleave, and each one is on disk once written.
+pub struct Checkpoint {
+    path: PathBuf,
+    sender: mpsc::UnboundedSender<String>,
+    writer: JoinHandle<io::Result<()>>,
+}
+
+impl Checkpoint {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let open_error = |e: io::Error| format!("could not open checkpoint {}: {}", path.display(), e);
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(path).map_err(open_error)?;
+        // End a line cut off by a killed run, so the first new entry starts on a line of its own
+        if file.metadata().map_err(open_error)?.len() > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1)).and_then(|_| file.read_exact(&mut last)).map_err(open_error)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n").map_err(open_error)?;
+            }
+        }
+        let mut file = tokio::fs::File::from_std(file);
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            while let Some(line) = receiver.recv().await {
+                file.write_all(line.as_bytes()).await?;
+                file.flush().await?;
+            }
+            Ok(())
+        });
+        Ok(Checkpoint { path: path.to_path_buf(), sender, writer })
+    }
+
+    /// Queues one finished check for writing.
+    pub fn record(&self, from: DateTime<Utc>, to: DateTime<Utc>, check: &UptimeResult) {
+        let entry = Entry { from, to, check: check.clone() };
+        if let Ok(mut line) = serde_json::to_string(&entry) {
+            line.push('\n');
+            // The writer only stops early after a write error, which `finish` reports
+            let _ = self.sender.send(line);
+        }
+    }
+
+    /// Waits until every queued line is written.
+    pub async fn finish(self) -> Result<(), Box<dyn Error>> {
+        drop(self.sender);
+        match self.writer.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(format!("could not write checkpoint {}: {}", self.path.display(), e).into()),
+            Err(e) => Err(format!("checkpoint writer for {} failed: {}", self.path.display(), e).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::uptime_result;
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_records_are_written_as_whole_lines() {
+        let path = std::env::temp_dir().join(format!("prt-checkpoint-concurrent-{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let window = (DateTime::UNIX_EPOCH, DateTime::from_timestamp(86_400, 0).unwrap());
+        let checkpoint = Arc::new(Checkpoint::open(&path).unwrap());
+
+        // Long names make every line larger than a single small write
+        let name = "x".repeat(4096);
+        let tasks: Vec<_> = (0..200)
+            .map(|id| {
+                let (checkpoint, name) = (checkpoint.clone(), name.clone());
+                tokio::spawn(async move { checkpoint.record(window.0, window.1, &uptime_result(id, &name, 86_000, 400)) })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        Arc::into_inner(checkpoint).unwrap().finish().await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let loaded = load(&path, &[window]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents.lines().count(), 200);
+        for line in contents.lines() {
+            serde_json::from_str::<Entry>(line).unwrap();
+        }
+        let mut ids: Vec<u64> = loaded.unwrap().remove(0).iter().map(|c| c.id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..200).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn entries_for_another_window_are_rejected() {
+        let path = std::env::temp_dir().join(format!("prt-checkpoint-window-{}.ndjson", std::process::id()));
+        let entry = Entry { from: DateTime::UNIX_EPOCH, to: DateTime::from_timestamp(3_600, 0).unwrap(), check: uptime_result(1, "web", 3_600, 0) };
+        std::fs::write(&path, format!("{}\n{{\"from\":", serde_json::to_string(&entry).unwrap())).unwrap();
+
+        let same = load(&path, &[(entry.from, entry.to)]);
+        let other = load(&path, &[(entry.from, DateTime::from_timestamp(7_200, 0).unwrap())]);
+        std::fs::remove_file(&path).unwrap();
+
+        // The cut-off last line is skipped
+        assert_eq!(same.unwrap()[0].len(), 1);
+        assert!(other.unwrap_err().to_string().contains("not this report's"));
+    }
+}
@@ -0,0 +1,655 @@
+use chrono::{FixedOffset, TimeDelta};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use crate::api::{Resolution, CHECKS_ENDPOINT_PARAMS, DEFAULT_MAX_PAYLOAD_BYTES, DEFAULT_POOL_IDLE_TIMEOUT_SECS, MAX_CHECKS_PAGE_SIZE};
+use crate::dates::{self, Period};
+use crate::fields::Field;
+use crate::maintenance::{parse_maintenance, MaintenanceWindow};
+use crate::output::{LineEnding, OutputFormat};
+use crate::report::{DowntimeRounding, SortKey};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Report options, used when no subcommand is given
+    #[command(flatten)]
+    pub report: ReportArgs,
+}
+
+// Options shared by every subcommand
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+    /// Pingdom API key (defaults to the PINGDOM_API_KEY environment variable)
+    #[arg(long, global = true)]
+    pub api_key: Option<String>,
+
+    /// Pingdom API base URL (defaults to the PINGDOM_API_URL environment variable)
+    #[arg(long, global = true)]
+    pub api_url: Option<String>,
+
+    /// Load environment variables from this file instead of ./.env
+    #[arg(long, global = true, value_name = "PATH")]
+    pub dotenv_path: Option<PathBuf>,
+
+    /// Header that carries the API key, for gateways that expect something other than Authorization
+    #[arg(long, global = true, default_value = "Authorization")]
+    pub auth_header_name: String,
+
+    /// Scheme placed before the API key in the auth header; pass an empty string to send the bare key
+    #[arg(long, global = true, default_value = "Bearer")]
+    pub auth_scheme: String,
+
+    /// Log each API request URL to stderr (credentials are never logged)
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Disable colored output (also disabled by NO_COLOR or when stdout is not a terminal)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Checks listed per /checks request; accounts with more are fetched in several pages
+    #[arg(long, global = true, default_value_t = NonZeroUsize::new(MAX_CHECKS_PAGE_SIZE).unwrap(), value_name = "N")]
+    pub fetch_checks_limit: NonZeroUsize,
+
+    /// Pass a query parameter through to the /checks request, e.g. tags=prod (repeatable)
+    #[arg(long, global = true, value_parser = parse_checks_param, value_name = "KEY=VALUE")]
+    pub checks_endpoint_params: Vec<(String, String)>,
+
+    /// Retry at most this many times in the whole run, across every check and account (429s and --probe-timeout-retries)
+    #[arg(long, global = true, value_name = "N")]
+    pub retry_budget: Option<u64>,
+
+    /// Stop sending requests for a while when more than this percentage of the recent ones fail
+    #[arg(long, global = true, value_name = "PCT", value_parser = parse_breaker_threshold)]
+    pub breaker_threshold: Option<f64>,
+
+    /// How many of the most recent requests --breaker-threshold looks at
+    #[arg(long, global = true, default_value_t = NonZeroUsize::new(20).unwrap(), value_name = "N", requires = "breaker_threshold")]
+    pub breaker_window: NonZeroUsize,
+
+    /// How long the circuit breaker stays open before a trial request is let through
+    #[arg(long, global = true, default_value_t = 30, value_name = "SECS", requires = "breaker_threshold")]
+    pub breaker_cooldown_secs: u64,
+
+    /// Fail a request whose response body (after decompression) is larger than this many bytes
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_PAYLOAD_BYTES, value_name = "BYTES")]
+    pub max_payload_bytes: usize,
+
+    /// Ask for uncompressed API responses (for debugging with a proxy or packet capture)
+    #[arg(long, global = true)]
+    pub no_compression: bool,
+
+    /// Also write every raw API response of the run to a new timestamped directory under DIR
+    #[arg(long, global = true, value_name = "DIR")]
+    pub archive_dir: Option<PathBuf>,
+
+    /// Keep at most this many idle connections to the API open for reuse (default: no limit)
+    #[arg(long, global = true, value_name = "N")]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Close connections that have been idle for this many seconds
+    #[arg(long, global = true, default_value_t = DEFAULT_POOL_IDLE_TIMEOUT_SECS, value_name = "SECS")]
+    pub pool_idle_timeout_secs: u64,
+
+    /// Skip TLS certificate verification, for a local mock or a gateway with a self-signed certificate
+    #[arg(long, global = true)]
+    pub insecure: bool,
+
+    /// Also trust the root certificates in this PEM file (default: $PINGDOM_CA_BUNDLE)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Allow --insecure even when the API URL is a real Pingdom host
+    #[arg(long, global = true, requires = "insecure")]
+    pub force_insecure: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Calculate uptime for checks over a date range (the default)
+    Report(Box<ReportArgs>),
+    /// List checks, optionally filtered
+    Checks(ChecksArgs),
+    /// List the tags used by checks and how many checks carry each
+    Tags,
+    /// List Pingdom's probe servers with their country and region
+    Probes(ProbesArgs),
+    /// Verify that the API credentials and URL work
+    SelfTest,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// Print the JSON Schema of the `--format json` report and exit
+    #[arg(long)]
+    pub json_schema: bool,
+
+    /// Start date in MM/DD/YYYY format (e.g., 01/01/2024), or days ago (e.g., 30d)
+    #[arg(short, long)]
+    pub start_date: Option<String>,
+
+    /// End date in MM/DD/YYYY format (e.g., 12/31/2024), or days ago (0d is today)
+    #[arg(short, long)]
+    pub end_date: Option<String>,
+
+    /// How dates appear in the human-readable output (strftime, e.g. "%d %b %Y"); JSON and CSV keep ISO 8601
+    #[arg(long, value_parser = dates::parse_date_format, value_name = "STRFTIME")]
+    pub date_format: Option<String>,
+
+    /// Timezone the dates are interpreted in: UTC or a fixed offset such as +02:00
+    #[arg(long, value_parser = dates::parse_timezone, default_value = "UTC", allow_hyphen_values = true)]
+    pub timezone: FixedOffset,
+
+    /// Snap the start date down and the end date up to whole periods
+    #[arg(long, value_enum)]
+    pub round_to_period: Option<Period>,
+
+    /// Also fetch the outage summary and report the number of outages per check
+    #[arg(long)]
+    pub with_outages: bool,
+
+    /// Also fetch each check's details and flag checks with no integrations or alert contacts
+    #[arg(long)]
+    pub with_alerting: bool,
+
+    /// Also include the figures of every --resolution bucket (weeks by default) as `breakdown` in JSON
+    #[arg(long)]
+    pub with_weekly_breakdown: bool,
+
+    /// Mark breakdown buckets far below the check's availability as incident candidates
+    #[arg(long, requires = "with_weekly_breakdown")]
+    pub flag_anomalies: bool,
+
+    /// Percentage points below the check's availability that make a bucket an anomaly
+    #[arg(long, default_value_t = 1.0, value_name = "PCT", requires = "flag_anomalies")]
+    pub anomaly_threshold: f64,
+
+    /// Also list availability per probe region for each check
+    #[arg(long)]
+    pub by_region: bool,
+
+    /// Report on another Pingdom account as NAME=API_KEY (repeatable); results are merged
+    #[arg(long, value_parser = parse_account, value_name = "NAME=API_KEY")]
+    pub account: Vec<(String, String)>,
+
+    /// How many accounts are processed at the same time, each with up to 10 checks in flight
+    #[arg(long, default_value = "1")]
+    pub parallel_accounts: NonZeroUsize,
+
+    /// Start the first checks of each account this many milliseconds apart instead of all at once
+    #[arg(long, value_name = "MS")]
+    pub ramp_ms: Option<u64>,
+
+    /// Tune how many checks run at once per account: grow while requests succeed, halve on a 429
+    #[arg(long)]
+    pub adaptive_concurrency: bool,
+
+    /// Checks in flight per account that --adaptive-concurrency starts at and never goes below
+    #[arg(long, default_value = "2", requires = "adaptive_concurrency")]
+    pub min_concurrency: NonZeroUsize,
+
+    /// Most checks in flight per account with --adaptive-concurrency
+    #[arg(long, default_value = "20", requires = "adaptive_concurrency")]
+    pub max_concurrency: NonZeroUsize,
+
+    /// Also fetch the average response time per day of each check, as `response_trend` in JSON
+    #[arg(long)]
+    pub response_trend: bool,
+
+    /// Also report each check's availability per day, week or month of the window, as `series`
+    /// in JSON or one column per period in CSV/TSV
+    #[arg(long, value_name = "PERIOD", conflicts_with_all = ["unsorted", "summary_only", "compare_periods", "baseline"])]
+    pub aggregate_by: Option<Period>,
+
+    /// Query the windows of --compare-periods, and the --aggregate-by series, concurrently
+    #[arg(long)]
+    pub parallel_dates: bool,
+
+    /// Only report these checks, by ID or exact name (comma-separated or repeated)
+    #[arg(long, visible_alias = "check", value_delimiter = ',', value_name = "ID|NAME")]
+    pub checks: Vec<String>,
+
+    /// Choose the checks to report from an interactive list that filters as you type
+    #[arg(long, conflicts_with_all = ["checks", "checks_file", "check_name", "serve"])]
+    pub pick: bool,
+
+    /// Only report the one check with this name, ignoring case; fails if none or several match
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["checks", "checks_file"])]
+    pub check_name: Option<String>,
+
+    /// Instead of the report, print Pingdom's raw performance summary of the one selected check
+    #[arg(long, conflicts_with_all = ["compare_periods", "baseline", "summary_only", "unsorted", "serve", "probe_breakdown", "redact"])]
+    pub raw: bool,
+
+    /// With --raw, also include the check's full detail from /checks/{id}
+    #[arg(long, requires = "raw")]
+    pub raw_detail: bool,
+
+    /// Render a report saved with `--format json` instead of querying Pingdom
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["start_date", "end_date", "round_to_period", "serve", "unsorted", "compare_periods", "probe_breakdown", "raw", "pick", "check_name", "redact"]
+    )]
+    pub from_report: Option<PathBuf>,
+
+    /// Replace check names with `check-<n>` labels and leave out targets, for sharing reports
+    #[arg(long)]
+    pub redact: bool,
+
+    /// Write a JSON file mapping each `--redact` label back to its check
+    #[arg(long, value_name = "FILE", requires = "redact")]
+    pub redact_map: Option<PathBuf>,
+
+    /// Instead of the report, calculate the one selected check from this Unix timestamp (with --probe-to)
+    #[arg(
+        long,
+        value_name = "EPOCH",
+        requires = "probe_to",
+        conflicts_with_all = ["start_date", "end_date", "round_to_period", "serve", "from_report", "compare_periods", "baseline", "summary_only", "unsorted", "probe_breakdown", "raw", "redact", "checkpoint"]
+    )]
+    pub probe_from: Option<i64>,
+
+    /// End of the --probe-from spot check, as a Unix timestamp
+    #[arg(long, value_name = "EPOCH", requires = "probe_from")]
+    pub probe_to: Option<i64>,
+
+    /// Instead of the report, list what each probe saw of the one selected check, most downtime first
+    #[arg(long, conflicts_with_all = ["compare_periods", "baseline", "summary_only", "unsorted", "serve", "redact"])]
+    pub probe_breakdown: bool,
+
+    /// Only report the checks listed in this file, one ID or name per line (`#` starts a comment)
+    #[arg(long, value_name = "PATH")]
+    pub checks_file: Option<PathBuf>,
+
+    /// Compute availability from the probes in a single region only (e.g. NA, EU, APAC)
+    #[arg(long, value_name = "REGION")]
+    pub probe_filter: Option<String>,
+
+    /// Count only confirmed downtime (inside the down states of Pingdom's outage summary) against availability
+    #[arg(long)]
+    pub exclude_unconfirmed: bool,
+
+    /// Leave downtime inside this window out of availability: START-END as Unix timestamps, or
+    /// HH:MM-HH:MM every day in --timezone (repeatable; one extra request per check)
+    #[arg(long, value_parser = parse_maintenance, value_name = "START-END")]
+    pub maintenance: Vec<MaintenanceWindow>,
+
+    /// Bucket size requested from Pingdom's performance summary
+    #[arg(long, value_enum, default_value_t = Resolution::Week)]
+    pub resolution: Resolution,
+
+    /// Ask Pingdom for uptime totals (default: only when the output uses them)
+    #[arg(long, value_name = "BOOL")]
+    pub include_uptime: Option<bool>,
+
+    /// How total downtime seconds are converted to whole minutes
+    #[arg(long, value_enum, default_value_t = DowntimeRounding::Floor)]
+    pub downtime_rounding: DowntimeRounding,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Record each finished check in this file and skip the checks it already has when resuming
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["serve", "from_report"])]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Render the report through this Handlebars template instead of a built-in format
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["format", "unsorted", "summary_only", "baseline", "compare_periods", "group_by_tag"])]
+    pub output_template: Option<PathBuf>,
+
+    /// Write the report to this file instead of stdout (replacing its contents unless --append)
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Fill in the {date}, {from} and {to} placeholders of the --output path, e.g. report-{date}.csv
+    #[arg(long, requires = "output")]
+    pub output_rotate: bool,
+
+    /// Add to the end of the --output file instead of replacing it; CSV/TSV still write their
+    /// header each run unless --no-header is given
+    #[arg(long, requires = "output")]
+    pub append: bool,
+
+    /// Replace an existing --output file without asking first
+    #[arg(short = 'y', long, requires = "output")]
+    pub assume_yes: bool,
+
+    /// Leave out the CSV/TSV header row, so runs can be concatenated
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// Start CSV/TSV output with a UTF-8 byte-order mark so Excel reads non-ASCII names correctly
+    #[arg(long)]
+    pub bom: bool,
+
+    /// Line terminator of the text, table, CSV and TSV output
+    #[arg(long, value_enum, default_value_t = LineEnding::Lf)]
+    pub line_ending: LineEnding,
+
+    /// Field separator of the CSV output, a single character such as ';'
+    #[arg(long, value_name = "CHAR")]
+    pub delimiter: Option<char>,
+
+    /// Order of the checks in non-JSON output (JSON and ndjson use --json-sort-by)
+    #[arg(long, value_enum, default_value_t = SortKey::Name)]
+    pub sort_by: SortKey,
+
+    /// Order of the checks in JSON and ndjson output: --json-sort-by if given, otherwise name; --sort-by applies only to non-JSON formats
+    #[arg(long, value_enum, value_name = "SORT_BY")]
+    pub json_sort_by: Option<SortKey>,
+
+    /// Group the text and table output by tag, with a subtotal per tag
+    #[arg(long)]
+    pub group_by_tag: bool,
+
+    /// With --group-by-tag, print only each tag's subtotal and a grand total, not the checks (text, json, csv, tsv)
+    #[arg(long, requires = "group_by_tag")]
+    pub group_summary_only: bool,
+
+    /// SLA target percentage each check is evaluated against (e.g. 99.9)
+    #[arg(long)]
+    pub sla: Option<f64>,
+
+    /// SLA target for checks with a tag, as TAG=PERCENT (repeatable, overrides --sla)
+    #[arg(long, value_parser = parse_tag_value)]
+    pub sla_per_tag: Vec<(String, f64)>,
+
+    /// Also report the downtime each check's SLA allows over the window and how much of it is left
+    #[arg(long)]
+    pub error_budget: bool,
+
+    /// Estimated cost of one minute of downtime, reported per check and in total
+    #[arg(long, value_name = "AMOUNT")]
+    pub cost_per_minute: Option<f64>,
+
+    /// Cost of a minute of downtime for checks with a tag, as TAG=AMOUNT (repeatable, overrides --cost-per-minute)
+    #[arg(long, value_parser = parse_tag_value)]
+    pub cost_per_tag: Vec<(String, f64)>,
+
+    /// Symbol placed before costs in the text output
+    #[arg(long, default_value = "$", value_name = "SYMBOL")]
+    pub currency_symbol: String,
+
+    /// Always pretty-print JSON, even when stdout is not a terminal
+    #[arg(long, conflicts_with = "compact")]
+    pub pretty: bool,
+
+    /// Always write compact JSON, even when stdout is a terminal
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Comma-separated columns for text/CSV/TSV output, in order (JSON always has every field)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub fields: Vec<Field>,
+
+    /// Rename a field in CSV/TSV headers and JSON keys, as FIELD=NAME (repeatable)
+    #[arg(long, value_parser = parse_rename)]
+    pub rename: Vec<(Field, String)>,
+
+    /// Cut check names longer than this in the text and table output (table default: fit the terminal)
+    #[arg(long, value_name = "N")]
+    pub name_width: Option<usize>,
+
+    /// Fail, listing the offenders, when a selected check's name in Pingdom is longer than this
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_name_length: Option<u64>,
+
+    /// Remove this text from the start of check names in the report (repeatable)
+    #[arg(long, value_name = "STR", allow_hyphen_values = true)]
+    pub strip_prefix: Vec<String>,
+
+    /// Remove this text from the end of check names in the report (repeatable)
+    #[arg(long, value_name = "STR", allow_hyphen_values = true)]
+    pub strip_suffix: Vec<String>,
+
+    /// Trim surrounding whitespace from check names in the report, after any stripping
+    #[arg(long)]
+    pub trim: bool,
+
+    /// Show when each check was last down in the text output (always included in JSON)
+    #[arg(long)]
+    pub show_last_down: bool,
+
+    /// Show when each check was created in the text output, flagging checks created during the window
+    #[arg(long)]
+    pub show_created: bool,
+
+    /// Also calculate the equally long window just before this one and show both with the change
+    #[arg(long, conflicts_with_all = ["baseline", "unsorted", "summary_only", "group_by_tag"])]
+    pub compare_periods: bool,
+
+    /// Compare against a report saved with --format json and print only the differences
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+
+    /// With --baseline, print the usual report of just the new checks and those that changed
+    #[arg(long, requires = "baseline")]
+    pub only_changed: bool,
+
+    /// Percentage points of availability (or of monitored time, for downtime) that count as a change
+    #[arg(long, default_value_t = 0.0, value_name = "PCT", requires = "only_changed")]
+    pub change_threshold: f64,
+
+    /// Exit with status 1 when any check is below this percentage
+    #[arg(long)]
+    pub fail_under: Option<f64>,
+
+    /// Only list the checks below their SLA or --fail-under; totals still cover every check
+    #[arg(long, conflicts_with = "baseline")]
+    pub only_failing: bool,
+
+    /// Print only the fleet-wide summary (the `aggregate` object in JSON), not each check
+    #[arg(long, conflicts_with_all = ["baseline", "unsorted", "group_by_tag"])]
+    pub summary_only: bool,
+
+    /// Add the median, standard deviation and nines tiers of availability across checks to the summary
+    #[arg(long)]
+    pub summary_statistics: bool,
+
+    /// Write each check as soon as it completes instead of sorting by name (text, ndjson, csv, tsv)
+    #[arg(long, conflicts_with_all = ["baseline", "group_by_tag"])]
+    pub unsorted: bool,
+
+    /// With --unsorted, keep no results after writing them; drops the text summary footer
+    #[arg(long, requires = "unsorted")]
+    pub low_memory: bool,
+
+    /// Print the number of API requests, retries, rate-limited responses and elapsed time to stderr
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Give up on a check that takes longer than this many seconds, including its extra requests
+    #[arg(long, value_name = "SECS")]
+    pub timeout_per_check: Option<u64>,
+
+    /// Mark checks backed by fewer than this many estimated tests as low confidence
+    #[arg(long, value_name = "N")]
+    pub probe_count_min: Option<u64>,
+
+    /// Recalculate a check that fails (an error or --timeout-per-check) up to this many times
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub probe_timeout_retries: u32,
+
+    /// Abort the whole run on the first check that cannot connect to Pingdom at all
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Fail the run when any check could not be calculated, instead of leaving it out
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Run as a long-lived exporter serving Prometheus metrics at /metrics
+    #[arg(long)]
+    pub serve: bool,
+
+    /// Address the metrics server listens on
+    #[arg(long, default_value = "127.0.0.1:9184", requires = "serve")]
+    pub listen: SocketAddr,
+
+    /// Seconds between report runs in --serve mode
+    #[arg(long, default_value_t = 300, requires = "serve")]
+    pub interval_secs: u64,
+
+    /// Length of the rolling window in --serve mode when no dates are given
+    #[arg(long, default_value_t = 30, requires = "serve")]
+    pub window_days: u64,
+}
+
+impl ReportArgs {
+    /// Whether the performance summary is requested with `includeuptime=true`. Left to itself this
+    /// is only skipped when `--fields` selects no uptime column and nothing else (an SLA, JSON, a
+    /// summary) needs the totals.
+    pub fn include_uptime(&self) -> bool {
+        if let Some(include) = self.include_uptime {
+            return include;
+        }
+        let fields_only = matches!(self.format, OutputFormat::Text | OutputFormat::Csv | OutputFormat::Tsv)
+            && !self.fields.is_empty()
+            && !self.fields.iter().any(|f| f.needs_uptime());
+        let needs_totals = self.sla.is_some()
+            || self.with_weekly_breakdown
+            || self.probe_count_min.is_some()
+            || !self.sla_per_tag.is_empty()
+            || self.cost_per_minute.is_some()
+            || !self.cost_per_tag.is_empty()
+            || self.fail_under.is_some()
+            || self.summary_only
+            || self.baseline.is_some()
+            || self.serve;
+        !fields_only || needs_totals
+    }
+
+    /// The order for the chosen format. JSON and ndjson use `--json-sort-by` if given, otherwise
+    /// `name`, so a committed report diffs cleanly; `--sort-by` applies only to non-JSON formats.
+    pub fn sort_key(&self) -> SortKey {
+        match (self.format, self.json_sort_by) {
+            (OutputFormat::Json | OutputFormat::Ndjson, key) => key.unwrap_or(SortKey::Name),
+            _ => self.sort_by,
+        }
+    }
+
+    /// The check name as reported: each `--strip-prefix` and `--strip-suffix` that matches is
+    /// removed once, in the order given, then `--trim` applies. Check selection still uses the
+    /// name as it is in Pingdom.
+    pub fn normalize_name(&self, name: &str) -> String {
+        let mut name = name;
+        for prefix in &self.strip_prefix {
+            name = name.strip_prefix(prefix.as_str()).unwrap_or(name);
+        }
+        for suffix in &self.strip_suffix {
+            name = name.strip_suffix(suffix.as_str()).unwrap_or(name);
+        }
+        if self.trim {
+            name = name.trim();
+        }
+        name.to_string()
+    }
+
+    /// Whether JSON should be indented: explicit flags win, otherwise only on a terminal.
+    pub fn pretty_json(&self) -> bool {
+        self.pretty || (!self.compact && self.output.is_none() && io::stdout().is_terminal())
+    }
+}
+
+fn parse_tag_value(value: &str) -> Result<(String, f64), String> {
+    let (tag, number) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected TAG=VALUE, got '{}'", value))?;
+    let number = number
+        .parse()
+        .map_err(|_| format!("invalid number '{}' for tag '{}'", number, tag))?;
+    Ok((tag.to_string(), number))
+}
+
+fn parse_account(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, key)) if !name.is_empty() && !key.is_empty() => Ok((name.to_string(), key.to_string())),
+        _ => Err("expected NAME=API_KEY".to_string()),
+    }
+}
+
+fn parse_checks_param(value: &str) -> Result<(String, String), String> {
+    let (key, param) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))?;
+    if !CHECKS_ENDPOINT_PARAMS.contains(&key) {
+        return Err(format!("unsupported /checks parameter '{}', expected one of {}", key, CHECKS_ENDPOINT_PARAMS.join(", ")));
+    }
+    Ok((key.to_string(), param.to_string()))
+}
+
+fn parse_breaker_threshold(value: &str) -> Result<f64, String> {
+    let threshold: f64 = value.parse().map_err(|_| format!("expected a percentage, got '{}'", value))?;
+    if !(0.0..100.0).contains(&threshold) {
+        return Err(format!("expected a percentage from 0 up to 100, got {}", threshold));
+    }
+    Ok(threshold)
+}
+
+fn parse_rename(value: &str) -> Result<(Field, String), String> {
+    let (field, name) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected FIELD=NAME, got '{}'", value))?;
+    let field = Field::from_str(field, false).map_err(|_| {
+        let known: Vec<String> = Field::value_variants().iter().map(|f| f.name()).collect();
+        format!("unknown field '{}', expected one of {}", field, known.join(", "))
+    })?;
+    if name.is_empty() {
+        return Err(format!("missing new name for field '{}'", field.name()));
+    }
+    Ok((field, name.to_string()))
+}
+
+#[derive(Args, Debug)]
+pub struct ChecksArgs {
+    /// Only list checks whose name contains this text (case-insensitive)
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Only list checks carrying this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Only list checks with this status (e.g. up, down, paused)
+    #[arg(long)]
+    pub status: Option<String>,
+
+    /// Only list checks not tested for longer than this (e.g. 30m, 6h, 2d), with how long ago
+    #[arg(long, value_name = "DURATION", value_parser = dates::parse_duration)]
+    pub stale_after: Option<TimeDelta>,
+}
+
+#[derive(Args, Debug)]
+pub struct ProbesArgs {
+    /// Only list probes in this region (e.g. NA, EU, APAC), as used by --probe-filter
+    #[arg(long)]
+    pub region: Option<String>,
+
+    /// Order of the list
+    #[arg(long, value_enum, default_value_t = ProbeSortKey::Id)]
+    pub sort_by: ProbeSortKey,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeSortKey {
+    /// Probe ID, ascending
+    Id,
+    /// Probe name, A to Z
+    Name,
+    /// Country, then name
+    Country,
+    /// Region, then country and name
+    Region,
+}
@@ -0,0 +1,52 @@
+//! Single gate for ANSI coloring. Every colored string in the tool goes through [`paint`], so
+//! turning color off in [`init`] disables it everywhere.
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Color {
+    Red,
+    Yellow,
+    Green,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Yellow => "33",
+            Color::Green => "32",
+        }
+    }
+}
+
+/// Enables color only when stdout is a terminal, `--no-color` was not given and `NO_COLOR` is
+/// unset or empty (see https://no-color.org).
+pub fn init(no_color: bool) {
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = !no_color && !no_color_env && io::stdout().is_terminal();
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn paint(text: &str, color: Color) -> String {
+    if ENABLED.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", color.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Color for an availability percentage: green at three nines or better, yellow from 99%, red below.
+pub fn for_percentage(percentage: f64) -> Color {
+    if percentage >= 99.9 {
+        Color::Green
+    } else if percentage >= 99.0 {
+        Color::Yellow
+    } else {
+        Color::Red
+    }
+}
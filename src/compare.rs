@@ -0,0 +1,155 @@
+//! Side-by-side comparison of the report window with the equally long window before it.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::color::{self, paint};
+use crate::report::{round4, Report, UptimeResult};
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct PeriodFigures {
+    /// `None` when nothing was monitored in that window
+    pub percentage: Option<f64>,
+    #[serde(rename = "downtime_minutes")]
+    pub downtime_mins: u64,
+}
+
+/// Current minus previous; a positive percentage delta is an improvement.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct Delta {
+    /// `None` unless both windows have a percentage
+    pub percentage: Option<f64>,
+    #[serde(rename = "downtime_minutes")]
+    pub downtime_mins: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CheckComparison {
+    pub id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    pub name: String,
+    /// `None` when the check is missing from that window, e.g. because it did not exist yet
+    pub current: Option<PeriodFigures>,
+    pub previous: Option<PeriodFigures>,
+    /// Present when the check is in both windows
+    pub delta: Option<Delta>,
+}
+
+/// The `--compare-periods` document: both windows and every check found in either of them.
+#[derive(Serialize, Clone, Debug)]
+pub struct Comparison {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub previous_from: DateTime<Utc>,
+    pub previous_to: DateTime<Utc>,
+    pub checks: Vec<CheckComparison>,
+    pub aggregate: CheckComparison,
+}
+
+fn figures(u: &UptimeResult) -> PeriodFigures {
+    PeriodFigures {
+        percentage: u.percentage,
+        downtime_mins: u.downtime_mins,
+    }
+}
+
+fn delta(current: Option<PeriodFigures>, previous: Option<PeriodFigures>) -> Option<Delta> {
+    let (current, previous) = (current?, previous?);
+    Some(Delta {
+        percentage: current.percentage.zip(previous.percentage).map(|(current, previous)| round4(current - previous)),
+        downtime_mins: current.downtime_mins as i64 - previous.downtime_mins as i64,
+    })
+}
+
+/// Matches checks by account and id. Checks keep the order of the current report, followed by
+/// the ones only found in the previous window.
+pub fn compare_reports(current: &Report, previous: &Report) -> Comparison {
+    let key = |u: &UptimeResult| (u.account.clone(), u.id);
+    let before: HashMap<_, _> = previous.checks.iter().map(|u| (key(u), u)).collect();
+    let now: HashMap<_, _> = current.checks.iter().map(|u| (key(u), u)).collect();
+
+    let mut checks: Vec<CheckComparison> = current
+        .checks
+        .iter()
+        .map(|u| {
+            let current = Some(figures(u));
+            let previous = before.get(&key(u)).map(|p| figures(p));
+            CheckComparison {
+                id: u.id,
+                account: u.account.clone(),
+                name: u.name.clone(),
+                current,
+                previous,
+                delta: delta(current, previous),
+            }
+        })
+        .collect();
+    checks.extend(previous.checks.iter().filter(|u| !now.contains_key(&key(u))).map(|u| CheckComparison {
+        id: u.id,
+        account: u.account.clone(),
+        name: u.name.clone(),
+        current: None,
+        previous: Some(figures(u)),
+        delta: None,
+    }));
+
+    let fleet = |report: &Report| {
+        report.aggregate.percentage.map(|percentage| PeriodFigures {
+            percentage: Some(percentage),
+            downtime_mins: report.aggregate.downtime_mins,
+        })
+    };
+    let (fleet_now, fleet_before) = (fleet(current), fleet(previous));
+
+    Comparison {
+        from: current.from,
+        to: current.to,
+        previous_from: previous.from,
+        previous_to: previous.to,
+        checks,
+        aggregate: CheckComparison {
+            id: 0,
+            account: None,
+            name: "All checks".to_string(),
+            current: fleet_now,
+            previous: fleet_before,
+            delta: delta(fleet_now, fleet_before),
+        },
+    }
+}
+
+fn write_comparison_line<W: Write>(w: &mut W, c: &CheckComparison) -> io::Result<()> {
+    let current = match c.current.map(|f| f.percentage) {
+        Some(Some(percentage)) => paint(&format!("{}%", percentage), color::for_percentage(percentage)),
+        Some(None) => "n/a".to_string(),
+        None => "-".to_string(),
+    };
+    let previous = match c.previous.map(|f| f.percentage) {
+        Some(Some(percentage)) => format!("{}%", percentage),
+        Some(None) => "n/a".to_string(),
+        None => "-".to_string(),
+    };
+    let name = match &c.account {
+        Some(account) => format!("{}/{}", account, c.name),
+        None => c.name.clone(),
+    };
+    write!(w, "{}, {}, previously {}", name, current, previous)?;
+    if let Some(delta) = c.delta {
+        let percentage = delta.percentage.map(|p| format!("{:+}", p)).unwrap_or_else(|| "n/a".to_string());
+        write!(w, ", {} ({:+} mins)", percentage, delta.downtime_mins)?;
+    }
+    writeln!(w)
+}
+
+/// One line per check, e.g. `prod-api, 99.95%, previously 99.98%, -0.03 (+12 mins)`, then
+/// the same for all checks combined.
+pub fn write_comparison_text<W: Write>(w: &mut W, comparison: &Comparison) -> io::Result<()> {
+    for c in &comparison.checks {
+        write_comparison_line(w, c)?;
+    }
+    writeln!(w)?;
+    write_comparison_line(w, &comparison.aggregate)
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Datelike, Days, FixedOffset, Months, NaiveDate, TimeDelta, TimeZone, Utc};
+use clap::ValueEnum;
+use std::fmt::Write;
+
+use crate::error::PingdomError;
+
+/// Parses a `--timezone` value: `UTC`/`Z` or a fixed offset such as `+02:00` or `-0500`.
+pub fn parse_timezone(tz: &str) -> Result<FixedOffset, String> {
+    if tz.eq_ignore_ascii_case("utc") || tz.eq_ignore_ascii_case("z") {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+    tz.parse::<FixedOffset>()
+        .map_err(|_| format!("invalid timezone '{}', expected UTC or an offset like +02:00", tz))
+}
+
+/// Parses a `--date-format` value. It is tried on a date up front, so a bad specifier or one
+/// that needs a time of day (`%H`) is rejected here instead of failing halfway through a report.
+pub fn parse_date_format(format: &str) -> Result<String, String> {
+    let mut sample = String::new();
+    match write!(sample, "{}", NaiveDate::MIN.format(format)) {
+        Ok(()) if !format.is_empty() => Ok(format.to_string()),
+        _ => Err(format!("invalid date format '{}', expected strftime specifiers for a date such as '%d %b %Y'", format)),
+    }
+}
+
+/// Parses a duration such as `90s`, `30m`, `6h` or `2d`.
+pub fn parse_duration(duration: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("invalid duration '{}', expected a number followed by s, m, h or d (e.g. 6h)", duration);
+    let split = duration.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let amount: i64 = duration[..split].parse().map_err(|_| invalid())?;
+    let delta = match &duration[split..] {
+        "s" => TimeDelta::try_seconds(amount),
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        _ => None,
+    };
+    delta.ok_or_else(invalid)
+}
+
+/// How long ago something happened, to the two largest units, e.g. `2d 3h` or `12m`.
+pub fn display_age(age: TimeDelta) -> String {
+    let minutes = age.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// A date for people: `--date-format`, or ISO 8601 without one.
+pub fn display_date(date: NaiveDate, format: Option<&str>) -> String {
+    date.format(format.unwrap_or("%Y-%m-%d")).to_string()
+}
+
+/// A timestamp for people: the date in `--date-format` followed by the time, or RFC 3339
+/// without a format.
+pub fn display_datetime(time: DateTime<FixedOffset>, format: Option<&str>) -> String {
+    match format {
+        Some(format) => format!("{} {}", time.format(format), time.format("%H:%M")),
+        None => time.to_rfc3339(),
+    }
+}
+
+/// Parses a `--start-date`/`--end-date`: MM/DD/YYYY, or `<N>d` for N days before today in
+/// `tz` (`0d` is today, so `-s 30d -e 0d` covers the last 30 whole days).
+pub fn parse_date(date_str: &str, tz: FixedOffset) -> Result<NaiveDate, PingdomError> {
+    if let Some(days) = date_str.strip_suffix('d').and_then(|n| n.parse::<u64>().ok()) {
+        let today = Utc::now().with_timezone(&tz).date_naive();
+        return today
+            .checked_sub_days(Days::new(days))
+            .ok_or_else(|| PingdomError::InvalidDate(format!("'{}' is too far in the past", date_str)));
+    }
+    NaiveDate::parse_from_str(date_str, "%m/%d/%Y").map_err(|e| {
+        PingdomError::InvalidDate(format!(
+            "invalid date '{}' ({}), expected MM/DD/YYYY or a number of days ago such as 30d",
+            date_str, e
+        ))
+    })
+}
+
+/// The instant at which `date` starts in the given timezone.
+pub fn start_of_day(date: NaiveDate, tz: FixedOffset) -> DateTime<Utc> {
+    tz.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+/// Converts a Pingdom epoch timestamp to the given timezone. Pingdom uses 0 (or omits the
+/// field) for "never", which maps to `None`.
+pub fn from_epoch(epoch: Option<i64>, tz: FixedOffset) -> Option<DateTime<FixedOffset>> {
+    match epoch {
+        Some(secs) if secs > 0 => DateTime::from_timestamp(secs, 0).map(|t| t.with_timezone(&tz)),
+        _ => None,
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Period {
+    /// Calendar months
+    Month,
+    /// ISO weeks, starting on Monday
+    Week,
+    /// Whole days
+    Day,
+}
+
+impl Period {
+    /// The start of the period containing `date`.
+    pub fn floor(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Month => date.with_day(1).unwrap(),
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Period::Day => date,
+        }
+    }
+
+    /// The first period boundary at or after `date`. End dates are exclusive, so an end date
+    /// that already sits on a boundary is left alone.
+    pub fn ceil(self, date: NaiveDate) -> NaiveDate {
+        let start = self.floor(date);
+        if start == date {
+            return date;
+        }
+        match self {
+            Period::Month => start + Months::new(1),
+            Period::Week => start + Days::new(7),
+            Period::Day => start + Days::new(1),
+        }
+    }
+
+    /// The start of every period that overlaps `from..to` (end exclusive), in order.
+    pub fn starts(self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let mut starts = Vec::new();
+        let mut start = self.floor(from);
+        while start < to {
+            starts.push(start);
+            start = self.ceil(start + Days::new(1));
+        }
+        starts
+    }
+}
@@ -0,0 +1,190 @@
+//! Comparison of a report against a saved baseline report.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::report::{round4, Report, UptimeResult};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Diff {
+    /// In the current report but not in the baseline
+    New { id: u64, name: String, percentage: Option<f64> },
+    /// In the baseline but not in the current report
+    Removed { id: u64, name: String, percentage: Option<f64> },
+    /// In both, with a different percentage (`None` without data); `delta` needs both
+    Changed { id: u64, name: String, before: Option<f64>, after: Option<f64>, delta: Option<f64> },
+}
+
+impl Diff {
+    fn sort_key(&self) -> (&str, u64) {
+        match self {
+            Diff::New { id, name, .. } | Diff::Removed { id, name, .. } | Diff::Changed { id, name, .. } => (name, *id),
+        }
+    }
+}
+
+/// Differences between two reports, matched by account and check id and sorted by name then
+/// id. Checks whose percentage is unchanged are left out.
+pub fn diff_reports(baseline: &Report, current: &Report) -> Vec<Diff> {
+    let before: HashMap<(&Option<String>, u64), _> = baseline.checks.iter().map(|c| ((&c.account, c.id), c)).collect();
+    let after: HashMap<(&Option<String>, u64), _> = current.checks.iter().map(|c| ((&c.account, c.id), c)).collect();
+
+    let mut diffs = Vec::new();
+    for c in &current.checks {
+        match before.get(&(&c.account, c.id)) {
+            None => diffs.push(Diff::New {
+                id: c.id,
+                name: c.name.clone(),
+                percentage: c.percentage,
+            }),
+            Some(b) if b.percentage != c.percentage => diffs.push(Diff::Changed {
+                id: c.id,
+                name: c.name.clone(),
+                before: b.percentage,
+                after: c.percentage,
+                delta: c.percentage.zip(b.percentage).map(|(after, before)| round4(after - before)),
+            }),
+            Some(_) => {}
+        }
+    }
+    for b in &baseline.checks {
+        if !after.contains_key(&(&b.account, b.id)) {
+            diffs.push(Diff::Removed {
+                id: b.id,
+                name: b.name.clone(),
+                percentage: b.percentage,
+            });
+        }
+    }
+
+    diffs.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    diffs
+}
+
+/// Keeps the checks of `current` that are new since `baseline` or moved more than `threshold`
+/// percentage points away from their baseline figures, either in availability or in downtime
+/// as a share of the time they were monitored. The latter catches changes the percentage hides
+/// when the baseline covers a window of another length. Returns the baseline checks that are
+/// gone from `current`. Checks are matched by account and id.
+pub fn retain_changed<'a>(baseline: &'a Report, current: &mut Report, threshold: f64) -> Vec<&'a UptimeResult> {
+    let before: HashMap<(&Option<String>, u64), &UptimeResult> =
+        baseline.checks.iter().map(|c| ((&c.account, c.id), c)).collect();
+    let removed = baseline
+        .checks
+        .iter()
+        .filter(|b| !current.checks.iter().any(|c| (&c.account, c.id) == (&b.account, b.id)))
+        .collect();
+
+    current.checks.retain(|c| match before.get(&(&c.account, c.id)) {
+        None => true,
+        Some(b) => {
+            let downtime_share = |u: &UptimeResult| match u.max_uptime {
+                0 => 0.0,
+                max_uptime => u.downtime as f64 * 100.0 / max_uptime as f64,
+            };
+            // Gaining or losing data altogether is a change whatever the threshold
+            let percentage_moved = match (c.percentage, b.percentage) {
+                (Some(after), Some(before)) => round4((after - before).abs()) > threshold,
+                (after, before) => after.is_some() != before.is_some(),
+            };
+            percentage_moved || round4((downtime_share(c) - downtime_share(b)).abs()) > threshold
+        }
+    });
+    removed
+}
+
+/// One line per difference, e.g. `CHANGED prod-api 99.98 -> 99.95 (-0.03)`. Checks without
+/// data show `n/a` instead of a percentage.
+pub fn write_diff_text<W: Write>(w: &mut W, diffs: &[Diff]) -> io::Result<()> {
+    let number = |percentage: &Option<f64>| percentage.map(|p| p.to_string()).unwrap_or_else(|| "n/a".to_string());
+    for diff in diffs {
+        match diff {
+            Diff::New { name, percentage, .. } => writeln!(w, "NEW {} {}", name, number(percentage))?,
+            Diff::Removed { name, percentage, .. } => writeln!(w, "REMOVED {} {}", name, number(percentage))?,
+            Diff::Changed { name, before, after, delta: Some(delta), .. } => {
+                writeln!(w, "CHANGED {} {} -> {} ({:+})", name, number(before), number(after), delta)?
+            }
+            Diff::Changed { name, before, after, delta: None, .. } => {
+                writeln!(w, "CHANGED {} {} -> {}", name, number(before), number(after))?
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::uptime_result;
+    use chrono::DateTime;
+
+    /// A report over a window of 10000 seconds, so every second down costs 0.01 percentage points.
+    fn report(checks: Vec<UptimeResult>) -> Report {
+        Report::new(DateTime::UNIX_EPOCH, DateTime::from_timestamp(10_000, 0).unwrap(), checks)
+    }
+
+    fn check(id: u64, name: &str, downtime: u64) -> UptimeResult {
+        uptime_result(id, name, 10_000 - downtime, downtime)
+    }
+
+    #[test]
+    fn diff_reports_lists_added_removed_and_changed_checks_by_name() {
+        let baseline = report(vec![check(1, "web", 10), check(2, "api", 50), check(3, "db", 0), check(4, "cron", 0)]);
+        let current = report(vec![check(2, "api", 10), check(1, "web", 50), check(3, "db", 0), check(5, "mail", 20)]);
+
+        assert_eq!(
+            diff_reports(&baseline, &current),
+            vec![
+                Diff::Changed { id: 2, name: "api".to_string(), before: Some(99.5), after: Some(99.9), delta: Some(0.4) },
+                Diff::Removed { id: 4, name: "cron".to_string(), percentage: Some(100.0) },
+                Diff::New { id: 5, name: "mail".to_string(), percentage: Some(99.8) },
+                Diff::Changed { id: 1, name: "web".to_string(), before: Some(99.9), after: Some(99.5), delta: Some(-0.4) },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_reports_matches_checks_by_account() {
+        let in_account = |account: &str, downtime| UptimeResult { account: Some(account.to_string()), ..check(1, "web", downtime) };
+        let baseline = report(vec![in_account("eu", 0), in_account("us", 50)]);
+        let current = report(vec![in_account("us", 50), in_account("eu", 0)]);
+
+        assert_eq!(diff_reports(&baseline, &current), Vec::new());
+    }
+
+    #[test]
+    fn diff_text_is_one_line_per_difference() {
+        let baseline = report(vec![check(1, "web", 10), check(4, "cron", 0)]);
+        let current = report(vec![check(1, "web", 50), check(5, "mail", 20)]);
+        let mut text = Vec::new();
+        write_diff_text(&mut text, &diff_reports(&baseline, &current)).unwrap();
+
+        assert_eq!(String::from_utf8(text).unwrap(), "REMOVED cron 100\nNEW mail 99.8\nCHANGED web 99.9 -> 99.5 (-0.4)\n");
+    }
+
+    #[test]
+    fn retain_changed_keeps_new_checks_and_those_beyond_the_threshold() {
+        let baseline = report(vec![check(1, "web", 10), check(2, "api", 10), check(3, "db", 10), check(4, "cron", 0)]);
+        // web moves by less than the threshold, api by exactly the threshold, db by more
+        let mut current = report(vec![check(1, "web", 15), check(2, "api", 20), check(3, "db", 30), check(5, "mail", 0)]);
+
+        let removed = retain_changed(&baseline, &mut current, 0.1);
+
+        let kept: Vec<&str> = current.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(kept, ["db", "mail"]);
+        let removed: Vec<&str> = removed.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(removed, ["cron"]);
+    }
+
+    #[test]
+    fn retain_changed_keeps_a_check_that_lost_its_data() {
+        let baseline = report(vec![check(1, "web", 0)]);
+        let mut current = report(vec![uptime_result(1, "web", 0, 0)]);
+
+        retain_changed(&baseline, &mut current, 5.0);
+
+        assert_eq!(current.checks.len(), 1);
+    }
+}
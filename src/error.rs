@@ -0,0 +1,82 @@
+//! The ways talking to the Pingdom API, and reading the inputs for it, can fail, so callers can
+//! react to the kind of failure (retry a rate limit, stop on a rejected key, ...) instead of to
+//! a message.
+
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::error::Error;
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PingdomError {
+    /// The API key was rejected: HTTP 401 or 403
+    #[error("Pingdom rejected the API key (HTTP {}): {message}", status.as_u16())]
+    Auth { status: StatusCode, message: String },
+
+    /// Still HTTP 429 Too Many Requests after every retry
+    #[error("rate limited by Pingdom after {retries} retries: {message}")]
+    RateLimited { retries: u32, message: String },
+
+    /// Any other error status, or an error object in a successful response
+    #[error("Pingdom API returned an error (HTTP {}): {message}", status.as_u16())]
+    Http { status: StatusCode, message: String },
+
+    /// A response that is not the JSON it should be: an HTML error page, a cut-off body, ...
+    #[error("{0}")]
+    Parse(String),
+
+    /// The request itself failed: connection refused, DNS, TLS, timeout, ...
+    #[error("{}: {}", .0, root_cause(.0))]
+    Network(#[from] reqwest::Error),
+
+    /// Not sent at all, because the `--breaker-threshold` circuit breaker is open
+    #[error("{0}")]
+    CircuitOpen(String),
+
+    /// A response larger than `--max-payload-bytes`
+    #[error("response from {url} is larger than {limit} bytes (raise --max-payload-bytes or use a coarser --resolution)")]
+    PayloadTooLarge { url: String, limit: usize },
+
+    /// Reading or writing a local file, such as the `--ca-bundle` or the `--archive-dir`
+    #[error("{context}: {source}")]
+    Io { context: String, source: io::Error },
+
+    /// A `--start-date`/`--end-date` that cannot be parsed
+    #[error("{0}")]
+    InvalidDate(String),
+
+    /// Client settings that cannot be used, e.g. an auth header name with spaces
+    #[error("{0}")]
+    Config(String),
+}
+
+/// The innermost error behind `e`, which says what actually went wrong (`Connection refused`,
+/// `certificate verify failed`, ...) where reqwest's own message does not.
+pub fn root_cause(e: &dyn Error) -> &dyn Error {
+    let mut cause = e;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause
+}
+
+impl PingdomError {
+    /// The error for an error response with `status`, using the message of Pingdom's error
+    /// object (`{"error": {"errormessage": ...}}`) when the body has one, else `fallback`.
+    pub fn from_status(status: StatusCode, body: Option<&Value>, retries: u32, fallback: &str) -> Self {
+        let message = body
+            .and_then(|b| b["error"]["errormessage"].as_str().or_else(|| b["error"]["statusdesc"].as_str()))
+            .unwrap_or(fallback)
+            .to_string();
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => PingdomError::Auth { status, message },
+            StatusCode::TOO_MANY_REQUESTS => PingdomError::RateLimited { retries, message },
+            _ => PingdomError::Http { status, message },
+        }
+    }
+
+    /// Whether Pingdom could not be reached at all, which no other request would get past either.
+    pub fn is_connect(&self) -> bool {
+        matches!(self, PingdomError::Network(e) if e.is_connect())
+    }
+}
@@ -0,0 +1,142 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::report::UptimeResult;
+
+/// A per-check column that can be selected with `--fields`. The names match the JSON keys.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[value(rename_all = "snake_case")]
+pub enum Field {
+    Id,
+    Account,
+    Name,
+    Target,
+    Tags,
+    Uptime,
+    Downtime,
+    Unmonitored,
+    MaxUptime,
+    Percentage,
+    #[value(name = "downtime_minutes", alias = "downtime_mins")]
+    DowntimeMins,
+    AvgResponseMs,
+    Outages,
+    Sla,
+    SlaMet,
+    #[value(name = "error_budget_minutes", alias = "error_budget_mins")]
+    ErrorBudgetMins,
+    #[value(name = "error_budget_remaining_minutes", alias = "error_budget_remaining_mins")]
+    ErrorBudgetRemainingMins,
+    ErrorBudgetExhausted,
+    DowntimeCost,
+    LastDown,
+    LastUp,
+    LastTest,
+    Created,
+    CreatedInWindow,
+    IncompleteData,
+    LowConfidence,
+    MaintenanceDowntime,
+    RawPercentage,
+    UnconfirmedDowntime,
+    Alerting,
+}
+
+/// Columns used by the delimited formats when `--fields` is not given.
+pub const DEFAULT_FIELDS: &[Field] = &[Field::Id, Field::Name, Field::Percentage, Field::DowntimeMins];
+
+impl Field {
+    pub fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+
+    /// The name used in output headers and JSON keys; the last matching `--rename` wins.
+    pub fn output_name(self, renames: &[(Field, String)]) -> String {
+        match renames.iter().rev().find(|(field, _)| *field == self) {
+            Some((_, name)) => name.clone(),
+            None => self.name(),
+        }
+    }
+
+    /// Whether the field is computed from the uptime totals, which Pingdom only returns with
+    /// `includeuptime=true`.
+    pub fn needs_uptime(self) -> bool {
+        matches!(
+            self,
+            Field::Uptime
+                | Field::Downtime
+                | Field::Unmonitored
+                | Field::MaxUptime
+                | Field::Percentage
+                | Field::DowntimeMins
+                | Field::Sla
+                | Field::SlaMet
+                | Field::ErrorBudgetMins
+                | Field::ErrorBudgetRemainingMins
+                | Field::ErrorBudgetExhausted
+                | Field::DowntimeCost
+                | Field::LowConfidence
+                | Field::MaintenanceDowntime
+                | Field::RawPercentage
+                | Field::UnconfirmedDowntime
+        )
+    }
+
+    /// The field's value rendered as plain text; missing optional values are empty, and so are
+    /// the uptime fields of a check without uptime data.
+    pub fn value(self, u: &UptimeResult) -> String {
+        fn opt<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        if self.needs_uptime() && u.max_uptime == 0 {
+            return String::new();
+        }
+
+        match self {
+            Field::Id => u.id.to_string(),
+            Field::Account => opt(u.account.as_ref()),
+            Field::Name => u.name.clone(),
+            Field::Target => opt(u.target.as_ref()),
+            Field::Tags => u.tags.join(";"),
+            Field::Uptime => u.uptime.to_string(),
+            Field::Downtime => u.downtime.to_string(),
+            Field::Unmonitored => u.unmonitored.to_string(),
+            Field::MaxUptime => u.max_uptime.to_string(),
+            Field::Percentage => opt(u.percentage),
+            Field::DowntimeMins => u.downtime_mins.to_string(),
+            Field::AvgResponseMs => opt(u.avg_response_ms),
+            Field::Outages => opt(u.outages),
+            Field::Sla => opt(u.sla),
+            Field::SlaMet => opt(u.sla_met),
+            Field::ErrorBudgetMins => opt(u.error_budget_mins),
+            Field::ErrorBudgetRemainingMins => opt(u.error_budget_remaining_mins),
+            Field::ErrorBudgetExhausted => opt(u.error_budget_exhausted),
+            Field::DowntimeCost => opt(u.downtime_cost),
+            Field::LastDown => opt(u.last_down.map(|t| t.to_rfc3339())),
+            Field::LastUp => opt(u.last_up.map(|t| t.to_rfc3339())),
+            Field::LastTest => opt(u.last_test.map(|t| t.to_rfc3339())),
+            Field::Created => opt(u.created.map(|t| t.to_rfc3339())),
+            Field::CreatedInWindow => u.created_in_window.to_string(),
+            Field::IncompleteData => u.incomplete_data.to_string(),
+            Field::LowConfidence => opt(u.low_confidence),
+            Field::MaintenanceDowntime => opt(u.maintenance_downtime),
+            Field::RawPercentage => opt(u.raw_percentage),
+            Field::UnconfirmedDowntime => opt(u.unconfirmed_downtime),
+            Field::Alerting => opt(u.alerting),
+        }
+    }
+}
+
+/// Applies `--rename` to the keys of one serialized check.
+pub fn rename_keys(check: &mut Value, renames: &[(Field, String)]) {
+    let Some(object) = check.as_object_mut() else {
+        return;
+    };
+    // Take every value out first so renames that swap two names do not clobber each other
+    let moved: Vec<(String, Value)> = renames
+        .iter()
+        .filter_map(|(field, name)| Some((name.clone(), object.remove(&field.name())?)))
+        .collect();
+    object.extend(moved);
+}
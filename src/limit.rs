@@ -0,0 +1,96 @@
+//! Adaptive concurrency for `--adaptive-concurrency`: an AIMD controller that lets more checks
+//! run while Pingdom keeps answering and halves the limit as soon as it answers 429.
+
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+struct State {
+    limit: usize,
+    in_flight: usize,
+    /// Checks finished without a new 429 since the limit last changed
+    successes: usize,
+    /// The client's 429 count when the last check finished
+    rate_limited: u64,
+}
+
+pub struct AdaptiveLimit {
+    min: usize,
+    max: usize,
+    label: String,
+    verbose: bool,
+    state: Mutex<State>,
+    released: Notify,
+}
+
+/// Held while a check runs; dropping it frees the slot.
+pub struct Slot<'a> {
+    limit: &'a AdaptiveLimit,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.limit.state.lock().unwrap().in_flight -= 1;
+        self.limit.released.notify_waiters();
+    }
+}
+
+impl AdaptiveLimit {
+    /// Starts at `min` checks in flight. `label` names the account in `--verbose` messages.
+    pub fn new(min: usize, max: usize, label: String, verbose: bool) -> Self {
+        AdaptiveLimit {
+            min,
+            max,
+            label,
+            verbose,
+            state: Mutex::new(State {
+                limit: min,
+                in_flight: 0,
+                successes: 0,
+                rate_limited: 0,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Waits until fewer checks than the current limit are in flight.
+    pub async fn acquire(&self) -> Slot<'_> {
+        loop {
+            // Register for the wakeup before checking, so a release in between is not missed
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return Slot { limit: self };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Called when a check finishes with the client's running 429 count. Any new 429 halves the
+    /// limit; otherwise it grows by one after a full limit's worth of checks.
+    pub fn record(&self, rate_limited: u64) {
+        let mut state = self.state.lock().unwrap();
+        let previous = state.limit;
+        if rate_limited > state.rate_limited {
+            state.limit = (state.limit / 2).max(self.min);
+            state.successes = 0;
+        } else {
+            state.successes += 1;
+            if state.successes >= state.limit {
+                state.limit = (state.limit + 1).min(self.max);
+                state.successes = 0;
+            }
+        }
+        state.rate_limited = rate_limited;
+
+        if state.limit != previous {
+            if self.verbose {
+                eprintln!("Concurrency{}: {} -> {}", self.label, previous, state.limit);
+            }
+            drop(state);
+            self.released.notify_waiters();
+        }
+    }
+}
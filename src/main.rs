@@ -1,181 +1,774 @@
-use reqwest::{Client, header};
+mod api;
+mod archive;
+mod breaker;
+mod checkpoint;
+mod cli;
+mod color;
+mod compare;
+mod dates;
+mod diff;
+mod error;
+mod fields;
+mod limit;
+mod maintenance;
+mod output;
+mod report;
+mod runner;
+mod serve;
+#[cfg(test)]
+mod testing;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
 use serde_json::Value;
-use chrono::{NaiveDate, DateTime, Utc, TimeZone};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, IsTerminal, Write};
 use clap::Parser;
 use dotenv::dotenv;
 use std::env;
-use futures::stream::{self, StreamExt};
-use tokio::time::{Duration, sleep};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Start date in MM/DD/YYYY format (e.g., 01/01/2024)
-    #[arg(short, long)]
-    start_date: Option<String>,
+use api::{check_tags, ClientOptions, PingdomApi, QuotaWindow, RequestStats, RetryBudget};
+use archive::ResponseArchive;
+use breaker::CircuitBreaker;
+use cli::{ChecksArgs, Cli, Command, GlobalArgs, ProbeSortKey, ProbesArgs, ReportArgs};
+use output::{CrlfWriter, LineEnding, OutputFormat, RenderOptions, StreamWriter};
+use report::{Report, Statistics};
+use runner::Account;
 
-    /// End date in MM/DD/YYYY format (e.g., 12/31/2024)
-    #[arg(short, long)]
-    end_date: Option<String>,
+fn print_usage() {
+    println!("Pingdom Uptime Calculator");
+    println!("Usage:");
+    println!("  pingdom --start-date <MM/DD/YYYY> --end-date <MM/DD/YYYY>");
+    println!("\nExample:");
+    println!("  pingdom --start-date 01/01/2024 --end-date 12/31/2024");
+    println!("\nOther commands:");
+    println!("  pingdom checks     List checks");
+    println!("  pingdom tags       List tags");
+    println!("  pingdom probes     List probe servers");
+    println!("  pingdom self-test  Verify API credentials");
+    println!("\nNote:");
+    println!("  Make sure to set the PINGDOM_API_KEY and PINGDOM_API_URL environment variables or add them to a .env file.");
 }
 
-#[derive(Clone)]
-struct PingdomApi {
-    pingdom_uri: String,
-    client: Client,
+fn connect(global: &GlobalArgs) -> Result<PingdomApi, Box<dyn Error>> {
+    let api_key = match &global.api_key {
+        Some(api_key) => api_key.clone(),
+        None => env::var("PINGDOM_API_KEY").map_err(|_| "PINGDOM_API_KEY must be set in environment or .env file")?,
+    };
+    Ok(PingdomApi::new(&api_key, &api_url(global)?, &client_options(global)?)?)
 }
 
-impl PingdomApi {
-    fn new(api_key: &str, pingdom_uri: &str) -> Self {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            header::HeaderValue::from_str(&format!("Bearer {}", api_key)).unwrap(),
-        );
+/// The API URL, which `--insecure` must not point at Pingdom itself unless `--force-insecure`
+/// says so: the key would go to whoever can intercept the connection.
+fn api_url(global: &GlobalArgs) -> Result<String, Box<dyn Error>> {
+    let api_url = match &global.api_url {
+        Some(api_url) => api_url.clone(),
+        None => env::var("PINGDOM_API_URL").map_err(|_| "PINGDOM_API_URL must be set in environment or .env file")?,
+    };
+    if global.insecure && !global.force_insecure {
+        let host = reqwest::Url::parse(&api_url).ok().and_then(|u| u.host_str().map(str::to_lowercase));
+        if let Some(host) = host.filter(|h| h == "pingdom.com" || h.ends_with(".pingdom.com")) {
+            return Err(format!(
+                "refusing --insecure for {}, a Pingdom host; it is meant for test endpoints (add --force-insecure to override)",
+                host
+            )
+            .into());
+        }
+    }
+    Ok(api_url)
+}
 
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()
-            .unwrap();
+/// The default account, or one client per `--account` (which all share the API URL).
+fn connect_accounts(global: &GlobalArgs, args: &ReportArgs) -> Result<Vec<Account>, Box<dyn Error>> {
+    if args.account.is_empty() {
+        return Ok(vec![Account { name: None, api: connect(global)? }]);
+    }
+    let api_url = api_url(global)?;
+    let options = client_options(global)?;
+    args.account
+        .iter()
+        .map(|(name, api_key)| {
+            let options = ClientOptions { account: Some(name.clone()), ..options.clone() };
+            Ok(Account {
+                name: Some(name.clone()),
+                api: PingdomApi::new(api_key, &api_url, &options)?,
+            })
+        })
+        .collect()
+}
 
-        PingdomApi {
-            pingdom_uri: pingdom_uri.to_string(),
-            client,
+/// Client settings from the global flags. With `--archive-dir` this creates the run's archive
+/// directory, which every account of the run shares.
+fn client_options(global: &GlobalArgs) -> Result<ClientOptions, Box<dyn Error>> {
+    let archive = match &global.archive_dir {
+        Some(root) => {
+            let archive = ResponseArchive::create(root)?;
+            eprintln!("Archiving API responses to {}", archive.dir().display());
+            Some(Arc::new(archive))
         }
+        None => None,
+    };
+    if global.insecure {
+        eprintln!("Warning: --insecure: TLS certificates are not verified, so anyone on the network path can read the API key and forge responses");
+    }
+    Ok(ClientOptions {
+        verbose: global.verbose,
+        auth_header_name: global.auth_header_name.clone(),
+        auth_scheme: global.auth_scheme.clone(),
+        compression: !global.no_compression,
+        checks_page_size: global.fetch_checks_limit.get(),
+        checks_params: global.checks_endpoint_params.clone(),
+        max_payload_bytes: global.max_payload_bytes,
+        pool_max_idle_per_host: global.pool_max_idle_per_host,
+        pool_idle_timeout: Duration::from_secs(global.pool_idle_timeout_secs),
+        archive,
+        account: None,
+        accept_invalid_certs: global.insecure,
+        ca_bundle: global.ca_bundle.clone().or_else(|| env::var_os("PINGDOM_CA_BUNDLE").filter(|v| !v.is_empty()).map(PathBuf::from)),
+        retry_budget: global.retry_budget.map(|limit| Arc::new(RetryBudget::new(limit))),
+        circuit_breaker: global.breaker_threshold.map(|threshold| {
+            Arc::new(CircuitBreaker::new(threshold, global.breaker_window.get(), Duration::from_secs(global.breaker_cooldown_secs)))
+        }),
+    })
+}
+
+/// The `--output` path. With `--output-rotate` its `{date}` (the day of the run), `{from}` and
+/// `{to}` (the first and last day of the report) placeholders are filled in as YYYY-MM-DD, in
+/// `--timezone`; a pattern without any of them, or with another one, is an error.
+fn output_path(args: &ReportArgs, from: NaiveDate, to: NaiveDate) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let Some(path) = &args.output else {
+        return Ok(None);
+    };
+    if !args.output_rotate {
+        return Ok(Some(path.clone()));
+    }
+    let pattern = path.to_str().ok_or("--output-rotate needs an --output path that is valid UTF-8")?;
+    let today = Utc::now().with_timezone(&args.timezone).date_naive();
+
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    let mut substituted = false;
+    while let Some(open) = rest.find('{') {
+        let close = open + rest[open..].find('}').ok_or_else(|| format!("unclosed '{{' in --output {}", pattern))?;
+        let day = match &rest[open + 1..close] {
+            "date" => today,
+            "from" => from,
+            "to" => to,
+            token => {
+                return Err(format!("unknown placeholder {{{}}} in --output {}, expected {{date}}, {{from}} or {{to}}", token, pattern).into())
+            }
+        };
+        expanded.push_str(&rest[..open]);
+        expanded.push_str(&day.format("%Y-%m-%d").to_string());
+        rest = &rest[close + 1..];
+        substituted = true;
+    }
+    expanded.push_str(rest);
+    if !substituted {
+        return Err(format!("--output-rotate needs a {{date}}, {{from}} or {{to}} placeholder in --output {}, e.g. report-{{date}}.csv", pattern).into());
     }
+    Ok(Some(PathBuf::from(expanded)))
+}
 
-    async fn get_checks(&self) -> Result<String, Box<dyn Error>> {
-        let response = self.client
-            .get(&format!("{}/checks", self.pingdom_uri))
-            .send()
-            .await?;
+/// Asks before an existing, non-empty `--output` file is replaced, so a manual run does not
+/// clobber last month's report. Appending, `--assume-yes` and runs outside a terminal (cron,
+/// pipes) go ahead without asking.
+fn confirm_overwrite(args: &ReportArgs, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let Some(path) = output else {
+        return Ok(());
+    };
+    if args.append || args.assume_yes || !path.metadata().is_ok_and(|m| m.is_file() && m.len() > 0) {
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(());
+    }
+    let overwrite = inquire::Confirm::new(&format!("{} already exists. Overwrite it?", path.display()))
+        .with_default(false)
+        .with_help_message("pass --assume-yes (-y) to skip this question")
+        .prompt()?;
+    if !overwrite {
+        return Err(format!("not overwriting {}; use --append to add to it or another --output path", path.display()).into());
+    }
+    Ok(())
+}
 
-        Ok(response.text().await?)
+/// Where the report is written: stdout, or the `--output` file (from `output_path`), truncated
+/// unless `--append`, starting with a byte-order mark when `--bom` asks for one.
+fn open_output(args: &ReportArgs, output: Option<&Path>) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    let (mut out, empty): (Box<dyn Write>, bool) = match output {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(args.append)
+                .truncate(!args.append)
+                .open(path)
+                .map_err(|e| format!("could not open output file {}: {}", path.display(), e))?;
+            let empty = file.metadata()?.len() == 0;
+            (Box::new(BufWriter::new(file)), empty)
+        }
+        None => (Box::new(io::stdout().lock()), true),
+    };
+    // A byte-order mark only belongs at the very start of a file, not before appended rows
+    if args.bom && empty {
+        out.write_all("\u{feff}".as_bytes())?;
     }
+    Ok(match args.line_ending {
+        LineEnding::Crlf => Box::new(CrlfWriter(out)),
+        LineEnding::Lf => out,
+    })
+}
 
-    async fn get_perf_summary(
-        &self,
-        check_id: &str,
-        from: &str,
-        to: &str,
-        includeuptime: &str,
-        resolution: &str,
-    ) -> Result<String, Box<dyn Error>> {
-        let url = format!(
-            "{}/summary.performance/{}?from={}&to={}&includeuptime={}&resolution={}",
-            self.pingdom_uri, check_id, from, to, includeuptime, resolution
-        );
+/// Locales whose decimal separator is a comma, by language code. Spreadsheets in these locales
+/// tend to expect `;` between CSV fields.
+const COMMA_DECIMAL_LANGUAGES: &[&str] = &["de", "fr", "es", "it", "nl", "pt", "ru", "pl", "sv", "da", "fi", "nb", "cs", "tr"];
 
-        let response = self.client.get(&url).send().await?;
+/// The language of the numeric locale, from the first of LC_ALL, LC_NUMERIC and LANG that is set.
+fn numeric_locale_language() -> Option<String> {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"].iter().find_map(|name| env::var(name).ok().filter(|v| !v.is_empty()))?;
+    Some(locale.split(['_', '.', '@']).next()?.to_lowercase())
+}
 
-        Ok(response.text().await?)
+/// The `--stats` line, summed over every account.
+fn print_stats(accounts: &[Account], started: Instant) {
+    let stats = accounts.iter().map(|a| a.api.stats()).fold(RequestStats::default(), |total, s| total + s);
+    eprintln!(
+        "Stats: {} requests, {} retries, {} rate limited (429), {} throttled, {} check retries, {:.1}s elapsed",
+        stats.requests,
+        stats.retries,
+        stats.rate_limited,
+        stats.throttled,
+        stats.check_retries,
+        started.elapsed().as_secs_f64()
+    );
+    // Each API key has a quota of its own
+    for account in accounts {
+        let quota = account.api.quota();
+        if quota.short.is_none() && quota.long.is_none() {
+            continue;
+        }
+        let describe = |window: Option<QuotaWindow>| match window {
+            Some(w) => {
+                let until_reset = TimeDelta::from_std(w.resets_at.saturating_duration_since(Instant::now())).unwrap_or_default();
+                format!("{} (resets in {})", w.remaining, dates::display_age(until_reset))
+            }
+            None => "unknown".to_string(),
+        };
+        let label = account.name.as_ref().map(|n| format!(" of {}", n)).unwrap_or_default();
+        eprintln!("Request quota{}: {} short-term, {} long-term left", label, describe(quota.short), describe(quota.long));
+    }
+    // Every account shares the one budget
+    if let Some(budget) = accounts.first().and_then(|a| a.api.retry_budget()) {
+        eprintln!("Retry budget: {} of {} used", budget.used(), budget.limit());
+    }
+    if let Some(breaker) = accounts.first().and_then(|a| a.api.circuit_breaker()) {
+        eprintln!("Circuit breaker: tripped {} times", breaker.trips());
     }
+}
 
-    async fn calculate_uptime(
-        &self,
-        check_id: &str,
-        check_name: &str,
-        from: &str,
-        to: &str,
-    ) -> Result<HashMap<String, Value>, Box<dyn Error>> {
-        let mut uptime_calc = HashMap::new();
-        uptime_calc.insert("id".to_string(), Value::String(check_id.to_string()));
-        uptime_calc.insert("name".to_string(), Value::String(check_name.to_string()));
-        uptime_calc.insert("uptime".to_string(), Value::Number(0.into()));
-        uptime_calc.insert("downtime".to_string(), Value::Number(0.into()));
-        uptime_calc.insert("unmonitored".to_string(), Value::Number(0.into()));
-        uptime_calc.insert("max_uptime".to_string(), Value::Number(0.into()));
-        uptime_calc.insert("percentage".to_string(), Value::Number(serde_json::Number::from_f64(0.0).unwrap()));
-        uptime_calc.insert("downtime_mins".to_string(), Value::Number(0.into()));
+async fn report(global: &GlobalArgs, args: &ReportArgs) -> Result<(), Box<dyn Error>> {
+    if args.json_schema {
+        serde_json::to_writer_pretty(io::stdout().lock(), &schemars::schema_for!(Report))?;
+        println!();
+        return Ok(());
+    }
+    if args.serve {
+        if global.retry_budget.is_some() {
+            return Err("--retry-budget does not work with --serve, the exporter would spend it for good".into());
+        }
+        let interrupted = runner::watch_interrupts();
+        return serve::serve(connect_accounts(global, args)?, args, args.listen, &interrupted).await;
+    }
 
-        let check_uptime: Value = serde_json::from_str(&self.get_perf_summary(check_id, from, to, "true", "week").await?)?;
+    if let (Some(from), Some(to)) = (args.probe_from, args.probe_to) {
+        return spot_check(global, args, from, to).await;
+    }
 
-        for u in check_uptime["summary"]["weeks"].as_array().unwrap() {
-            let uptime = uptime_calc["uptime"].as_u64().unwrap() + u["uptime"].as_u64().unwrap();
-            let downtime = uptime_calc["downtime"].as_u64().unwrap() + u["downtime"].as_u64().unwrap();
-            let downtime_mins = uptime_calc["downtime_mins"].as_u64().unwrap() + u["downtime"].as_u64().unwrap() / 60;
-            let unmonitored = uptime_calc["unmonitored"].as_u64().unwrap() + u["unmonitored"].as_u64().unwrap();
+    let saved = args.from_report.as_deref().map(Report::load).transpose()?;
+    let dates = match (&saved, &args.start_date, &args.end_date) {
+        (Some(_), _, _) => None,
+        (None, Some(start_date), Some(end_date)) => Some((start_date, end_date)),
+        _ => {
+            print_usage();
+            return Ok(());
+        }
+    };
 
-            uptime_calc.insert("uptime".to_string(), Value::Number(uptime.into()));
-            uptime_calc.insert("downtime".to_string(), Value::Number(downtime.into()));
-            uptime_calc.insert("downtime_mins".to_string(), Value::Number(downtime_mins.into()));
-            uptime_calc.insert("unmonitored".to_string(), Value::Number(unmonitored.into()));
+    if args.format == OutputFormat::Junit && args.sla.is_none() && args.fail_under.is_none() {
+        return Err("--format junit needs --sla or --fail-under to decide which checks fail".into());
+    }
+    if args.summary_only && matches!(args.format, OutputFormat::Junit | OutputFormat::Influx | OutputFormat::CsvWide) {
+        return Err("--summary-only does not work with --format junit, influx or csv-wide".into());
+    }
+    if args.group_summary_only {
+        if !matches!(args.format, OutputFormat::Text | OutputFormat::Json | OutputFormat::Csv | OutputFormat::Tsv) {
+            return Err("--group-summary-only only works with --format text, json, csv or tsv".into());
+        }
+    } else if args.group_by_tag && !matches!(args.format, OutputFormat::Text | OutputFormat::Table) {
+        return Err("--group-by-tag only works with --format text or table".into());
+    }
+    if args.compare_periods && !matches!(args.format, OutputFormat::Text | OutputFormat::Json | OutputFormat::Ndjson) {
+        return Err("--compare-periods only works with --format text, json or ndjson".into());
+    }
+    if args.bom && !matches!(args.format, OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::CsvWide) {
+        return Err("--bom only works with --format csv, tsv or csv-wide".into());
+    }
+    if args.delimiter.is_some() && !matches!(args.format, OutputFormat::Csv | OutputFormat::CsvWide) {
+        return Err("--delimiter only works with --format csv or csv-wide".into());
+    }
+    if let Some(delimiter) = args.delimiter {
+        if !delimiter.is_ascii() || matches!(delimiter, '"' | '\r' | '\n') {
+            return Err(format!("--delimiter cannot be {:?}", delimiter).into());
+        }
+        if delimiter == ',' {
+            if let Some(language) = numeric_locale_language().filter(|l| COMMA_DECIMAL_LANGUAGES.contains(&l.as_str())) {
+                eprintln!(
+                    "Warning: the '{}' locale writes decimals with a comma; spreadsheets there may split on it, consider --delimiter ';'",
+                    language
+                );
+            }
         }
+    }
+    if args.line_ending == LineEnding::Crlf && !matches!(args.format, OutputFormat::Text | OutputFormat::Table | OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::CsvWide) {
+        return Err("--line-ending only works with --format text, table, csv, tsv or csv-wide".into());
+    }
+    if args.only_failing && args.sla.is_none() && args.sla_per_tag.is_empty() && args.fail_under.is_none() {
+        return Err("--only-failing needs --sla, --sla-per-tag or --fail-under to decide which checks fail".into());
+    }
+    if args.error_budget && args.sla.is_none() && args.sla_per_tag.is_empty() {
+        return Err("--error-budget needs --sla or --sla-per-tag".into());
+    }
+    if args.probe_breakdown && !matches!(args.format, OutputFormat::Text | OutputFormat::Json) {
+        return Err("--probe-breakdown only works with --format text or json".into());
+    }
+    if args.append && matches!(args.format, OutputFormat::Json | OutputFormat::Junit) {
+        return Err("--append does not work with --format json or junit, the file would no longer be one document (use ndjson)".into());
+    }
+    if args.response_trend && !matches!(args.format, OutputFormat::Json | OutputFormat::Ndjson) {
+        return Err("--response-trend only works with --format json or ndjson".into());
+    }
+    if args.aggregate_by.is_some()
+        && !matches!(args.format, OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::CsvWide)
+    {
+        return Err("--aggregate-by only works with --format json, ndjson, csv, tsv or csv-wide".into());
+    }
+    if args.format == OutputFormat::CsvWide && args.aggregate_by.is_none() {
+        return Err("--format csv-wide needs --aggregate-by for its periods".into());
+    }
+    if args.min_concurrency > args.max_concurrency {
+        return Err("--min-concurrency cannot be larger than --max-concurrency".into());
+    }
+    if args.unsorted && !args.format.is_streamable() {
+        return Err("--unsorted only works with --format text, ndjson, csv or tsv".into());
+    }
 
-        let max_uptime = uptime_calc["uptime"].as_u64().unwrap() + uptime_calc["downtime"].as_u64().unwrap() + uptime_calc["unmonitored"].as_u64().unwrap();
-        uptime_calc.insert("max_uptime".to_string(), Value::Number(max_uptime.into()));
+    let (start_day, end_day, banner) = match (dates, &saved) {
+        (Some((start_date, end_date)), _) => {
+            let mut start_date = dates::parse_date(start_date, args.timezone)?;
+            let mut end_date = dates::parse_date(end_date, args.timezone)?;
+            if let Some(period) = args.round_to_period {
+                start_date = period.floor(start_date);
+                end_date = period.ceil(end_date);
+            }
+            (start_date, end_date, "Calculating uptime")
+        }
+        (None, Some(saved)) => (
+            saved.from.with_timezone(&args.timezone).date_naive(),
+            saved.to.with_timezone(&args.timezone).date_naive(),
+            "Rendering saved report",
+        ),
+        (None, None) => unreachable!("dates are required without --from-report"),
+    };
 
-        let percentage = ((uptime_calc["uptime"].as_u64().unwrap() as f64 + uptime_calc["unmonitored"].as_u64().unwrap() as f64) / max_uptime as f64 * 100.0 * 10000.0).round() / 10000.0;
-        uptime_calc.insert("percentage".to_string(), Value::Number(serde_json::Number::from_f64(percentage).unwrap()));
+    let output = output_path(args, start_day, end_day)?;
+    confirm_overwrite(args, output.as_deref())?;
 
-        Ok(uptime_calc)
+    // Keep stdout clean for machine-readable formats
+    let date_format = args.date_format.as_deref();
+    let banner = format!(
+        "{} from {} to {}",
+        banner,
+        dates::display_date(start_day, date_format),
+        dates::display_date(end_day, date_format)
+    );
+    match args.format {
+        OutputFormat::Text | OutputFormat::Table if !args.raw && args.output_template.is_none() => println!("{}", banner),
+        _ => eprintln!("{}", banner),
     }
-}
 
-fn parse_date(date_str: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
-    let naive_date = NaiveDate::parse_from_str(date_str, "%m/%d/%Y")?;
-    Ok(Utc.from_utc_datetime(&naive_date.and_hms_opt(0, 0, 0).unwrap()))
-}
+    let start_date = dates::start_of_day(start_day, args.timezone);
+    let end_date = dates::start_of_day(end_day, args.timezone);
 
-fn print_usage() {
-    println!("Pingdom Uptime Calculator");
-    println!("Usage:");
-    println!("  pingdom --start-date <MM/DD/YYYY> --end-date <MM/DD/YYYY>");
-    println!("\nExample:");
-    println!("  pingdom --start-date 01/01/2024 --end-date 12/31/2024");
-    println!("\nNote:");
-    println!("  Make sure to set the PINGDOM_API_KEY and PINGDOM_API_URL environment variables or add them to a .env file.");
-}
+    let baseline = args.baseline.as_deref().map(Report::load).transpose()?;
+    let template = args.output_template.as_deref().map(output::load_template).transpose()?;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    dotenv().ok(); // Load .env file if it exists
+    // Colors are for terminals, not files
+    if output.is_some() {
+        color::init(true);
+    }
+
+    let started = Instant::now();
+    // A saved report is rendered entirely offline
+    let accounts = if saved.is_some() { Vec::new() } else { connect_accounts(global, args)? };
+    let options = RenderOptions::from(args);
+    // A check without data has no percentage to be below the threshold with
+    let below_threshold = |percentage: Option<f64>| args.fail_under.zip(percentage).is_some_and(|(t, percentage)| percentage < t);
+    let shown = |u: &report::UptimeResult| !args.only_failing || u.sla_met == Some(false) || below_threshold(u.percentage);
 
-    let args = Args::parse();
+    if args.probe_breakdown {
+        let (name, probes) = runner::probe_breakdown(&accounts, args, start_date, end_date).await?;
+        let mut out = open_output(args, output.as_deref())?;
+        output::write_probe_breakdown(&mut out, &name, &probes, args.format, args.pretty_json())?;
+        out.flush()?;
+        if args.stats || global.verbose {
+            print_stats(&accounts, started);
+        }
+        return Ok(());
+    }
 
-    if args.start_date.is_none() || args.end_date.is_none() {
-        print_usage();
+    if args.raw {
+        let dump = runner::raw_dump(&accounts, args, start_date, end_date).await?;
+        let mut out = open_output(args, output.as_deref())?;
+        serde_json::to_writer_pretty(&mut out, &dump)?;
+        writeln!(out)?;
+        out.flush()?;
+        if args.stats || global.verbose {
+            print_stats(&accounts, started);
+        }
         return Ok(());
     }
 
-    let start_date = parse_date(&args.start_date.unwrap())?;
-    let end_date = parse_date(&args.end_date.unwrap())?;
+    // One flag for every window below, so a Ctrl-C stops the ones not started yet too
+    let interrupted = runner::watch_interrupts();
+    let failing = if args.unsorted {
+        let mut stream = StreamWriter::new(open_output(args, output.as_deref())?, args.format, &options)?;
+        let mut failing = 0;
+        let checks = runner::collect_results(&accounts, args, start_date, end_date, &interrupted, !args.low_memory, |u| {
+            if below_threshold(u.percentage) {
+                failing += 1;
+            }
+            if !shown(u) {
+                return Ok(());
+            }
+            stream.write_row(u)
+        })
+        .await?;
+        let mut out = stream.finish()?;
+        if args.format == OutputFormat::Text && !args.low_memory {
+            let mut report = Report::new(start_date, end_date, checks);
+            if args.summary_statistics {
+                report.aggregate.statistics = Some(Statistics::from_checks(&report.checks));
+            }
+            output::write_text_footer(&mut out, &report, &options)?;
+        }
+        out.flush()?;
+        failing
+    } else {
+        // The window of the same length that ends where this one starts
+        let previous_window = args.compare_periods.then(|| (start_date - (end_date - start_date), start_date));
+        let (mut report, mut previous) = match (saved, previous_window) {
+            (Some(saved), _) => (runner::reannotate(args, saved), None),
+            (None, Some(window)) if args.parallel_dates => {
+                let mut reports = runner::run_reports(&accounts, args, &[(start_date, end_date), window], &interrupted).await?.into_iter();
+                (reports.next().unwrap(), reports.next())
+            }
+            (None, Some((previous_start, previous_end))) => (
+                runner::run_report(&accounts, args, start_date, end_date, &interrupted).await?,
+                Some(runner::run_report(&accounts, args, previous_start, previous_end, &interrupted).await?),
+            ),
+            (None, None) => (runner::run_report(&accounts, args, start_date, end_date, &interrupted).await?, None),
+        };
+        args.sort_key().sort(&mut report.checks);
+        if args.summary_statistics {
+            report.aggregate.statistics = Some(Statistics::from_checks(&report.checks));
+        }
+        let failing = report.checks.iter().filter(|c| below_threshold(c.percentage)).count();
+        // The aggregate was computed over every check, so it keeps describing the whole fleet
+        report.checks.retain(|c| shown(c));
+        if let Some(previous) = &mut previous {
+            // Leave out the previous figures of hidden checks too, or they would show as removed
+            previous.checks.retain(|p| report.checks.iter().any(|c| (c.id, &c.account) == (p.id, &p.account)));
+        }
+        if let Some(baseline) = baseline.as_ref().filter(|_| args.only_changed) {
+            for b in diff::retain_changed(baseline, &mut report, args.change_threshold) {
+                eprintln!("Removed since the baseline: {} ({})", b.name, b.id);
+            }
+        }
+        let mut out = open_output(args, output.as_deref())?;
+        if let Some(baseline) = baseline.as_ref().filter(|_| !args.only_changed) {
+            let diffs = diff::diff_reports(baseline, &report);
+            match args.format {
+                OutputFormat::Json | OutputFormat::Ndjson => {
+                    serde_json::to_writer(&mut out, &diffs)?;
+                    writeln!(out)?;
+                }
+                _ => diff::write_diff_text(&mut out, &diffs)?,
+            }
+        } else if let Some(previous) = &previous {
+            let comparison = compare::compare_reports(&report, previous);
+            match args.format {
+                OutputFormat::Json => {
+                    if args.pretty_json() {
+                        serde_json::to_writer_pretty(&mut out, &comparison)?;
+                    } else {
+                        serde_json::to_writer(&mut out, &comparison)?;
+                    }
+                    writeln!(out)?;
+                }
+                OutputFormat::Ndjson => {
+                    for c in &comparison.checks {
+                        serde_json::to_writer(&mut out, c)?;
+                        writeln!(out)?;
+                    }
+                }
+                _ => compare::write_comparison_text(&mut out, &comparison)?,
+            }
+        } else if let Some(template) = &template {
+            output::write_template(&mut out, template, &report)?;
+        } else if args.group_summary_only {
+            output::write_group_summary(&mut out, &report, args.format, args.pretty_json(), &options)?;
+        } else if args.summary_only {
+            output::write_summary(&mut out, &report, args.format, args.pretty_json(), &options)?;
+        } else {
+            match args.format {
+                OutputFormat::Text => output::write_text(&mut out, &report, &options)?,
+                OutputFormat::Table => output::write_table(&mut out, &report, &options)?,
+                OutputFormat::Json => output::write_json(&mut out, &report, args.pretty_json(), &args.rename)?,
+                OutputFormat::Ndjson => output::write_ndjson(&mut out, &report, &args.rename)?,
+                OutputFormat::Csv | OutputFormat::Tsv => match args.aggregate_by {
+                    Some(period) => {
+                        let periods = period.starts(start_day, end_day);
+                        output::write_series_delimited(&mut out, &report, &periods, args.format, &options)?
+                    }
+                    None => output::write_delimited(&mut out, &report, args.format, &options)?,
+                },
+                OutputFormat::Junit => output::write_junit(&mut out, &report, args.fail_under)?,
+                OutputFormat::Influx => output::write_influx(&mut out, &report)?,
+                OutputFormat::CsvWide => {
+                    let periods = args.aggregate_by.map(|period| period.starts(start_day, end_day)).unwrap_or_default();
+                    output::write_series_wide(&mut out, &report, &periods, &options)?
+                }
+            }
+        }
+        out.flush()?;
+        failing
+    };
+
+    if (args.stats || global.verbose) && args.from_report.is_none() {
+        print_stats(&accounts, started);
+    }
+
+    if let Some(threshold) = args.fail_under {
+        if failing > 0 {
+            eprintln!("{} checks are below {}%", failing, threshold);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// `--probe-from`/`--probe-to`: one check over an arbitrary window, apart from the report path.
+async fn spot_check(global: &GlobalArgs, args: &ReportArgs, from: i64, to: i64) -> Result<(), Box<dyn Error>> {
+    let timestamp = |epoch: i64| DateTime::from_timestamp(epoch, 0).ok_or_else(|| format!("invalid timestamp {}", epoch));
+    let (from, to) = (timestamp(from)?, timestamp(to)?);
+    if from >= to {
+        return Err("--probe-from must be before --probe-to".into());
+    }
+    if !matches!(args.format, OutputFormat::Text | OutputFormat::Json) {
+        return Err("--probe-from only works with --format text or json".into());
+    }
+
+    let day = |time: DateTime<Utc>| time.with_timezone(&args.timezone).date_naive();
+    let output = output_path(args, day(from), day(to))?;
+    confirm_overwrite(args, output.as_deref())?;
 
-    let uptime_from = start_date.timestamp();
-    let uptime_to = end_date.timestamp();
+    let started = Instant::now();
+    let accounts = connect_accounts(global, args)?;
+    let result = runner::spot_check(&accounts, args, from, to).await?;
+    let mut out = open_output(args, output.as_deref())?;
+    output::write_spot_check(&mut out, &result, from, to, args.format, args.pretty_json())?;
+    out.flush()?;
+    if args.stats || global.verbose {
+        print_stats(&accounts, started);
+    }
+    Ok(())
+}
 
-    println!("Calculating uptime from {} to {}", start_date.format("%Y-%m-%d"), end_date.format("%Y-%m-%d"));
+async fn list_checks(global: &GlobalArgs, args: &ChecksArgs) -> Result<(), Box<dyn Error>> {
+    let pingdom_api = connect(global)?;
+    let all_checks = pingdom_api.get_checks().await?;
 
-    let api_key = env::var("PINGDOM_API_KEY").expect("PINGDOM_API_KEY must be set in environment or .env file");
-    let api_url = env::var("PINGDOM_API_URL").expect("PINGDOM_API_URL must be set in environment or .env file");
-    let pingdom_api = PingdomApi::new(&api_key, &api_url);
-    let all_checks: Value = serde_json::from_str(&pingdom_api.get_checks().await?)?;
+    let name_filter = args.name.as_ref().map(|n| n.to_lowercase());
+    let mut checks: Vec<&Value> = all_checks["checks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|c| match &name_filter {
+            Some(name) => c["name"].as_str().unwrap_or_default().to_lowercase().contains(name),
+            None => true,
+        })
+        .filter(|c| match &args.tag {
+            Some(tag) => check_tags(c).any(|t| t == tag),
+            None => true,
+        })
+        .filter(|c| match &args.status {
+            Some(status) => c["status"].as_str() == Some(status.as_str()),
+            None => true,
+        })
+        .collect();
+    checks.sort_by_key(|c| c["name"].as_str().unwrap_or_default().to_string());
 
-    let uptime_calculations = stream::iter(all_checks["checks"].as_array().unwrap())
-        .map(|c| {
-            let pingdom_api = pingdom_api.clone();
-            let check_id = c["id"].to_string();
-            let check_name = c["name"].to_string();
-            let uptime_from = uptime_from.to_string();
-            let uptime_to = uptime_to.to_string();
-            async move {
-                let result = pingdom_api.calculate_uptime(&check_id, &check_name, &uptime_from, &uptime_to).await;
-                sleep(Duration::from_millis(200)).await; // Add a small delay to avoid rate limiting
-                result
+    // Ages are measured against Pingdom's clock, which set the test times
+    let now = pingdom_api.server_time().unwrap_or_else(Utc::now);
+    let last_tested = |c: &Value| c["lasttesttime"].as_i64().filter(|&t| t > 0).and_then(|t| DateTime::from_timestamp(t, 0));
+    for c in checks {
+        let line = format!(
+            "{}, {}, {}, {}",
+            c["id"],
+            c["name"].as_str().unwrap_or_default(),
+            c["type"].as_str().unwrap_or_default(),
+            c["status"].as_str().unwrap_or_default()
+        );
+        match (args.stale_after, last_tested(c)) {
+            (None, _) => println!("{}", line),
+            (Some(threshold), Some(tested)) if now - tested > threshold => {
+                println!("{}, last tested {} ago", line, dates::display_age(now - tested))
             }
+            (Some(_), Some(_)) => {}
+            (Some(_), None) => println!("{}, never tested", line),
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_tags(global: &GlobalArgs) -> Result<(), Box<dyn Error>> {
+    let pingdom_api = connect(global)?;
+    let all_checks = pingdom_api.get_checks().await?;
+
+    let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+    for c in all_checks["checks"].as_array().into_iter().flatten() {
+        for tag in check_tags(c) {
+            *tags.entry(tag).or_default() += 1;
+        }
+    }
+
+    for (tag, count) in tags {
+        println!("{}, {} checks", tag, count);
+    }
+
+    Ok(())
+}
+
+async fn list_probes(global: &GlobalArgs, args: &ProbesArgs) -> Result<(), Box<dyn Error>> {
+    let pingdom_api = connect(global)?;
+    let all_probes = pingdom_api.get_probes().await?;
+
+    let text = |p: &Value, key: &str| p[key].as_str().unwrap_or_default().to_string();
+    let mut probes: Vec<&Value> = all_probes["probes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|p| match &args.region {
+            Some(region) => text(p, "region").eq_ignore_ascii_case(region),
+            None => true,
         })
-        .buffer_unordered(10) // Process up to 10 requests concurrently
-        .collect::<Vec<_>>()
-        .await;
+        .collect();
+    probes.sort_by_key(|p| {
+        let id = p["id"].as_u64().unwrap_or_default();
+        match args.sort_by {
+            ProbeSortKey::Id => (String::new(), String::new(), String::new(), id),
+            ProbeSortKey::Name => (text(p, "name"), String::new(), String::new(), id),
+            ProbeSortKey::Country => (text(p, "country"), text(p, "name"), String::new(), id),
+            ProbeSortKey::Region => (text(p, "region"), text(p, "country"), text(p, "name"), id),
+        }
+    });
 
-    let mut uptime_calculations: Vec<_> = uptime_calculations.into_iter().filter_map(Result::ok).collect();
-    uptime_calculations.sort_by(|a, b| a["name"].to_string().cmp(&b["name"].to_string()));
+    for p in probes {
+        let inactive = if p["active"].as_bool() == Some(false) { ", inactive" } else { "" };
+        println!(
+            "{}, {}, {} ({}), {}{}",
+            p["id"],
+            text(p, "name"),
+            text(p, "country"),
+            text(p, "countryiso"),
+            text(p, "region"),
+            inactive
+        );
+    }
 
-    for u in uptime_calculations {
-        println!("{}, {}%, {} mins", u["name"], u["percentage"], u["downtime_mins"]);
+    Ok(())
+}
+
+async fn self_test(global: &GlobalArgs) -> Result<(), Box<dyn Error>> {
+    let pingdom_api = connect(global)?;
+    let all_checks = pingdom_api.get_checks().await?;
+
+    if let Some(message) = all_checks["error"]["errormessage"].as_str() {
+        return Err(format!("Pingdom API returned an error: {}", message).into());
     }
 
+    let count = all_checks["checks"].as_array().map(Vec::len).unwrap_or(0);
+    println!("OK: connected to Pingdom, {} checks visible", count);
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Replaces Rust's panic output with a short bug-report request. With `RUST_BACKTRACE` set the
+/// default output, backtrace included, is printed instead.
+fn install_panic_hook() {
+    if env::var_os("RUST_BACKTRACE").is_some() {
+        return;
+    }
+    std::panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+        let location = info.location().map(|l| format!(" at {}:{}", l.file(), l.line())).unwrap_or_default();
+        eprintln!("prt {} hit an unexpected error: {}{}", env!("CARGO_PKG_VERSION"), message, location);
+        eprintln!("This is a bug. Please file an issue with this message and the --raw output of the check it failed on (the same command with --check <ID> --raw).");
+        eprintln!("Set RUST_BACKTRACE=1 to include a backtrace.");
+    }));
+}
+
+#[tokio::main]
+async fn main() {
+    install_panic_hook();
+    // Printed with Display rather than the Debug output of a `main` that returns the error
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    // An explicit file must exist; the default ./.env is optional
+    match &cli.global.dotenv_path {
+        Some(path) => {
+            dotenv::from_path(path).map_err(|e| format!("could not load env file {}: {}", path.display(), e))?;
+        }
+        None => {
+            dotenv().ok();
+        }
+    }
+    color::init(cli.global.no_color);
+
+    // Without a subcommand the top-level report options apply, as before subcommands existed
+    match cli.command {
+        Some(Command::Report(args)) => report(&cli.global, &args).await,
+        Some(Command::Checks(args)) => list_checks(&cli.global, &args).await,
+        Some(Command::Tags) => list_tags(&cli.global).await,
+        Some(Command::Probes(args)) => list_probes(&cli.global, &args).await,
+        Some(Command::SelfTest) => self_test(&cli.global).await,
+        None => report(&cli.global, &cli.report).await,
+    }
+}
@@ -0,0 +1,89 @@
+//! `--maintenance`: scheduled windows whose downtime does not count against availability.
+
+use chrono::{DateTime, FixedOffset, NaiveTime, TimeDelta, Utc};
+use serde_json::Value;
+
+/// One `--maintenance` window.
+#[derive(Clone, Copy, Debug)]
+pub enum MaintenanceWindow {
+    /// A single window between two Unix timestamps
+    Once(i64, i64),
+    /// Every day between two times of day in `--timezone`; an end before the start is on the
+    /// next day
+    Daily(NaiveTime, NaiveTime),
+}
+
+/// Parses `START-END`: two Unix timestamps (`1704420000-1704427200`) or two times of day for a
+/// daily window (`02:00-04:00`, `23:30-00:30`).
+pub fn parse_maintenance(value: &str) -> Result<MaintenanceWindow, String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected START-END, got '{}'", value))?;
+    if let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        if start >= end {
+            return Err(format!("the window '{}' ends before it starts", value));
+        }
+        return Ok(MaintenanceWindow::Once(start, end));
+    }
+    let time = |t: &str| {
+        NaiveTime::parse_from_str(t, "%H:%M")
+            .map_err(|_| format!("expected Unix timestamps or HH:MM times of day in '{}'", value))
+    };
+    let (start, end) = (time(start)?, time(end)?);
+    if start == end {
+        return Err(format!("the daily window '{}' is empty", value));
+    }
+    Ok(MaintenanceWindow::Daily(start, end))
+}
+
+/// The windows that fall within `from..to`, as Unix time intervals, cut to the report window,
+/// sorted and merged so that no second is in two of them.
+pub fn intervals(windows: &[MaintenanceWindow], from: DateTime<Utc>, to: DateTime<Utc>, tz: FixedOffset) -> Vec<(i64, i64)> {
+    let mut intervals = Vec::new();
+    for window in windows {
+        match *window {
+            MaintenanceWindow::Once(start, end) => intervals.push((start, end)),
+            MaintenanceWindow::Daily(start, end) => {
+                // Start a day early for a window that began before `from` and runs past midnight
+                let mut day = from.with_timezone(&tz).date_naive() - TimeDelta::days(1);
+                let last_day = to.with_timezone(&tz).date_naive();
+                while day <= last_day {
+                    let local = |time: NaiveTime, days: i64| (day + TimeDelta::days(days)).and_time(time).and_local_timezone(tz).unwrap().timestamp();
+                    intervals.push((local(start, 0), local(end, if end < start { 1 } else { 0 })));
+                    day += TimeDelta::days(1);
+                }
+            }
+        }
+    }
+
+    let (from, to) = (from.timestamp(), to.timestamp());
+    let mut clipped: Vec<(i64, i64)> = intervals
+        .into_iter()
+        .map(|(start, end)| (start.max(from), end.min(to)))
+        .filter(|(start, end)| start < end)
+        .collect();
+    clipped.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(clipped.len());
+    for (start, end) in clipped {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Seconds of the down periods of an outage summary (`summary.states`) that fall inside the
+/// (merged) `intervals`.
+pub fn downtime_within(outage_summary: &Value, intervals: &[(i64, i64)]) -> u64 {
+    let overlap = |from: i64, to: i64| -> i64 {
+        intervals.iter().map(|&(start, end)| (to.min(end) - from.max(start)).max(0)).sum()
+    };
+    outage_summary["summary"]["states"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|s| s["status"] == "down")
+        .filter_map(|s| Some(overlap(s["timefrom"].as_i64()?, s["timeto"].as_i64()?)))
+        .sum::<i64>() as u64
+}
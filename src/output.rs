@@ -0,0 +1,946 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use handlebars::Handlebars;
+use serde::Serialize;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::cli::ReportArgs;
+use crate::color::{self, paint, Color};
+use crate::dates;
+use crate::fields::{self, Field, DEFAULT_FIELDS};
+use crate::report::{self, Aggregate, ProbeUptime, Report, UptimeResult};
+
+fn colored_percentage(percentage: f64) -> String {
+    paint(&format!("{}%", percentage), color::for_percentage(percentage))
+}
+
+/// A check's or bucket's percentage, or `n/a` when nothing was monitored.
+fn percentage_or_na(percentage: Option<f64>) -> String {
+    percentage.map(colored_percentage).unwrap_or_else(|| "n/a".to_string())
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One line per check: name, percentage, downtime minutes
+    Text,
+    /// Aligned columns with a header, sized to the terminal
+    Table,
+    /// The full report as a single JSON document
+    Json,
+    /// One JSON object per check per line
+    Ndjson,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+    /// JUnit XML with one test case per check, failing below the SLA (needs --sla or --fail-under)
+    Junit,
+    /// InfluxDB line protocol, one `pingdom` point per check at the end of the window
+    Influx,
+    /// CSV with one row per --aggregate-by period and one availability column per check
+    CsvWide,
+}
+
+/// Line terminator of the text, table, CSV and TSV output.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+/// Turns every `\n` written through it into `\r\n`, for `--line-ending crlf`.
+pub struct CrlfWriter<W>(pub W);
+
+impl<W: Write> Write for CrlfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for chunk in buf.split_inclusive(|&b| b == b'\n') {
+            match chunk.strip_suffix(b"\n") {
+                Some(line) => {
+                    self.0.write_all(line)?;
+                    self.0.write_all(b"\r\n")?;
+                }
+                None => self.0.write_all(chunk)?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Rendering settings that are not implied by the report data itself.
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    pub show_last_down: bool,
+    pub show_created: bool,
+    /// Columns selected with `--fields`; empty means the default layout of each format
+    pub fields: Vec<Field>,
+    /// Output names from `--rename`, applied to delimited headers and JSON keys
+    pub renames: Vec<(Field, String)>,
+    /// Longest check name shown in the text and table output before it is cut with an ellipsis
+    pub name_width: Option<usize>,
+    /// Split the text and table output into one section per tag
+    pub group_by_tag: bool,
+    /// Write the CSV/TSV header row; off with `--no-header`
+    pub header: bool,
+    /// CSV field separator from `--delimiter`; a comma when unset
+    pub delimiter: Option<char>,
+    /// Placed before costs in the text output
+    pub currency_symbol: String,
+    /// `--date-format` for the dates in the text output; ISO 8601 when unset
+    pub date_format: Option<String>,
+}
+
+impl From<&ReportArgs> for RenderOptions {
+    fn from(args: &ReportArgs) -> Self {
+        RenderOptions {
+            show_last_down: args.show_last_down,
+            show_created: args.show_created,
+            fields: args.fields.clone(),
+            renames: args.rename.clone(),
+            name_width: args.name_width.or_else(|| match args.format {
+                OutputFormat::Table if args.output.is_none() => default_name_width(),
+                _ => None,
+            }),
+            group_by_tag: args.group_by_tag,
+            header: !args.no_header,
+            delimiter: args.delimiter,
+            currency_symbol: args.currency_symbol.clone(),
+            date_format: args.date_format.clone(),
+        }
+    }
+}
+
+/// What is left of the terminal after the table's other columns; `None` when stdout is not a
+/// terminal, so piped tables keep full names.
+fn default_name_width() -> Option<usize> {
+    let (terminal_size::Width(columns), _) = terminal_size::terminal_size_of(io::stdout())?;
+    Some((columns as usize).saturating_sub(TABLE_COLUMNS_WIDTH).max(12))
+}
+
+/// Room taken by the uptime and downtime columns with their separators.
+const TABLE_COLUMNS_WIDTH: usize = 24;
+
+/// The check name as shown in text output, prefixed with its account in multi-account runs.
+fn display_name(u: &UptimeResult) -> String {
+    match &u.account {
+        Some(account) => format!("{}/{}", account, u.name),
+        None => u.name.clone(),
+    }
+}
+
+/// Cuts a name to `width` characters, ending in an ellipsis when anything was removed.
+fn truncate_name(name: &str, width: Option<usize>) -> String {
+    match width {
+        Some(width) if name.chars().count() > width => {
+            let mut truncated: String = name.chars().take(width.saturating_sub(1)).collect();
+            truncated.push('…');
+            truncated
+        }
+        _ => name.to_string(),
+    }
+}
+
+impl OutputFormat {
+    /// Formats that can be written one check at a time, as results complete.
+    pub fn is_streamable(self) -> bool {
+        matches!(self, OutputFormat::Text | OutputFormat::Ndjson | OutputFormat::Csv | OutputFormat::Tsv)
+    }
+}
+
+/// How often a streamed report is flushed, so rows show up while later checks are still being
+/// calculated without a write to the sink per row.
+const STREAM_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes a streamable format one check at a time, as results complete. CSV and TSV rows go
+/// through a `csv::Writer`, so only the rows since the last flush are held in memory.
+pub struct StreamWriter<'a, W: Write> {
+    sink: Sink<W>,
+    format: OutputFormat,
+    options: &'a RenderOptions,
+    last_flush: Instant,
+}
+
+enum Sink<W: Write> {
+    Plain(W),
+    Delimited(Box<csv::Writer<W>>),
+}
+
+impl<'a, W: Write> StreamWriter<'a, W> {
+    /// Starts the stream, writing whatever precedes the first row (the CSV/TSV header).
+    pub fn new(w: W, format: OutputFormat, options: &'a RenderOptions) -> io::Result<Self> {
+        let sink = match format {
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                let mut writer = delimited_writer(w, format, options);
+                write_delimited_header(&mut writer, format, options)?;
+                Sink::Delimited(Box::new(writer))
+            }
+            _ => Sink::Plain(w),
+        };
+        Ok(StreamWriter { sink, format, options, last_flush: Instant::now() })
+    }
+
+    /// Writes a single check, flushing when the last flush is more than a second ago.
+    pub fn write_row(&mut self, u: &UptimeResult) -> io::Result<()> {
+        let written = match &mut self.sink {
+            Sink::Delimited(writer) => write_delimited_row(writer, self.format, u, self.options),
+            Sink::Plain(w) => match self.format {
+                OutputFormat::Text => write_text_row(w, u, self.options),
+                OutputFormat::Ndjson => write_ndjson_row(w, u, &self.options.renames),
+                _ => Ok(()),
+            },
+        };
+        written.map_err(|e| row_error(u, e))?;
+        if self.last_flush.elapsed() >= STREAM_FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        match &mut self.sink {
+            Sink::Plain(w) => w.flush(),
+            Sink::Delimited(writer) => writer.flush(),
+        }
+    }
+
+    /// Flushes the remaining rows and hands back the sink, e.g. for the text footer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.sink {
+            Sink::Plain(mut w) => {
+                w.flush()?;
+                Ok(w)
+            }
+            Sink::Delimited(writer) => writer.into_inner().map_err(|e| e.into_error()),
+        }
+    }
+}
+
+/// Writes the text report. Optional columns appear when the report carries the data for them
+/// (e.g. outage counts with `--with-outages`).
+pub fn write_text<W: Write>(w: &mut W, report: &Report, options: &RenderOptions) -> io::Result<()> {
+    if options.group_by_tag {
+        for (i, (tag, checks)) in report::group_by_tag(&report.checks).into_iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+            }
+            writeln!(w, "== {} ==", tag)?;
+            for u in &checks {
+                write_text_row(w, u, options)?;
+            }
+            write_subtotal(w, &Aggregate::from_checks(&checks))?;
+        }
+    } else {
+        for u in &report.checks {
+            write_text_row(w, u, options)?;
+        }
+    }
+    write_text_footer(w, report, options)
+}
+
+fn write_subtotal<W: Write>(w: &mut W, aggregate: &Aggregate) -> io::Result<()> {
+    match aggregate.percentage {
+        Some(percentage) => writeln!(w, "Subtotal, {}, {} mins", colored_percentage(percentage), aggregate.downtime_mins),
+        None => writeln!(w, "Subtotal, no data"),
+    }
+}
+
+fn write_text_row<W: Write>(w: &mut W, u: &UptimeResult, options: &RenderOptions) -> io::Result<()> {
+    if !options.fields.is_empty() {
+        let values: Vec<String> = options
+            .fields
+            .iter()
+            .map(|f| match f {
+                Field::Percentage => percentage_or_na(u.percentage),
+                _ => f.value(u),
+            })
+            .collect();
+        return writeln!(w, "{}", values.join(", "));
+    }
+
+    let percentage = match (u.percentage, u.sla_met) {
+        (Some(percentage), Some(met)) => paint(&format!("{}%", percentage), if met { Color::Green } else { Color::Red }),
+        (percentage, _) => percentage_or_na(percentage),
+    };
+    write!(w, "{}, {}, {} mins", truncate_name(&display_name(u), options.name_width), percentage, u.downtime_mins)?;
+    if let (Some(raw), Some(excluded)) = (u.raw_percentage, u.maintenance_downtime) {
+        write!(w, ", {}s in maintenance (raw {}%)", excluded, raw)?;
+    }
+    if let Some(unconfirmed) = u.unconfirmed_downtime {
+        write!(w, ", {}s unconfirmed", unconfirmed)?;
+    }
+    if let Some(outages) = u.outages {
+        write!(w, ", {} outages", outages)?;
+    }
+    if let (Some(sla), Some(met)) = (u.sla, u.sla_met) {
+        write!(w, ", SLA {}% {}", sla, if met { "PASS" } else { "FAIL" })?;
+    }
+    if let (Some(budget), Some(remaining)) = (u.error_budget_mins, u.error_budget_remaining_mins) {
+        if remaining > 0.0 {
+            write!(w, ", budget {:.1} of {:.1} mins left", remaining, budget)?;
+        } else {
+            write!(w, ", {} ({:.1} of {:.1} mins used)", paint("BUDGET EXHAUSTED", Color::Red), budget - remaining, budget)?;
+        }
+    }
+    if let Some(cost) = u.downtime_cost {
+        write!(w, ", cost {}{:.2}", options.currency_symbol, cost)?;
+    }
+    if u.alerting == Some(false) {
+        write!(w, ", {}", paint("NO ALERTING", Color::Red))?;
+    }
+    if u.incomplete_data {
+        write!(w, ", {}", paint("INCOMPLETE DATA", Color::Yellow))?;
+    }
+    if u.low_confidence == Some(true) {
+        write!(w, ", {}", paint("LOW CONFIDENCE", Color::Yellow))?;
+    }
+    let date_format = options.date_format.as_deref();
+    if options.show_last_down {
+        match (u.last_down, u.last_up) {
+            (Some(down), Some(up)) if up >= down => write!(
+                w,
+                ", last down {} to {}",
+                dates::display_datetime(down, date_format),
+                dates::display_datetime(up, date_format)
+            )?,
+            (Some(down), _) => write!(w, ", down since {}", dates::display_datetime(down, date_format))?,
+            _ => write!(w, ", never down")?,
+        }
+    }
+    if options.show_created {
+        if let Some(created) = u.created {
+            write!(w, ", created {}", dates::display_datetime(created, date_format))?;
+        }
+        if u.created_in_window {
+            write!(w, " {}", paint("(partial window)", Color::Yellow))?;
+        }
+    }
+    writeln!(w)?;
+
+    write_regions(w, u)
+}
+
+fn write_regions<W: Write>(w: &mut W, u: &UptimeResult) -> io::Result<()> {
+    match &u.regions {
+        Some(regions) if !regions.is_empty() => {
+            for (region, percentage) in regions {
+                writeln!(w, "    {}: {}", region, colored_percentage(*percentage))?;
+            }
+        }
+        Some(_) => writeln!(w, "    (no regional data)")?,
+        None => {}
+    }
+
+    Ok(())
+}
+
+/// Writes the report as aligned columns: names on the left, numbers right-aligned. Outage and
+/// SLA columns appear when the report carries that data. With `--group-by-tag` every tag gets a
+/// titled sub-table with a subtotal row, all sharing the same column widths.
+pub fn write_table<W: Write>(w: &mut W, report: &Report, options: &RenderOptions) -> io::Result<()> {
+    let layout = TableLayout {
+        name_width: report
+            .checks
+            .iter()
+            .map(|u| truncate_name(&display_name(u), options.name_width).chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(if options.group_by_tag { "SUBTOTAL".len() } else { "NAME".len() }),
+        with_outages: report.checks.iter().any(|u| u.outages.is_some()),
+        with_sla: report.checks.iter().any(|u| u.sla.is_some()),
+    };
+
+    if options.group_by_tag {
+        for (i, (tag, checks)) in report::group_by_tag(&report.checks).into_iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+            }
+            writeln!(w, "{}", tag)?;
+            write_table_rows(w, &checks, &layout, options)?;
+            let subtotal = Aggregate::from_checks(&checks);
+            let percentage = match subtotal.percentage {
+                Some(percentage) => colored_percentage_cell(percentage, color::for_percentage(percentage)),
+                None => format!("{:>9}", "-"),
+            };
+            writeln!(w, "{:<width$}  {}  {:>9}", "SUBTOTAL", percentage, format!("{} mins", subtotal.downtime_mins), width = layout.name_width)?;
+        }
+    } else {
+        write_table_rows(w, &report.checks, &layout, options)?;
+    }
+
+    write_text_footer(w, report, options)
+}
+
+struct TableLayout {
+    name_width: usize,
+    with_outages: bool,
+    with_sla: bool,
+}
+
+/// Pads before painting so the color codes do not count towards the column width.
+fn colored_percentage_cell(percentage: f64, color: Color) -> String {
+    paint(&format!("{:>9}", format!("{}%", percentage)), color)
+}
+
+fn write_table_rows<W: Write>(w: &mut W, checks: &[UptimeResult], layout: &TableLayout, options: &RenderOptions) -> io::Result<()> {
+    let name_width = layout.name_width;
+    write!(w, "{:<name_width$}  {:>9}  {:>9}", "NAME", "UPTIME", "DOWNTIME")?;
+    if layout.with_outages {
+        write!(w, "  {:>7}", "OUTAGES")?;
+    }
+    if layout.with_sla {
+        write!(w, "  {:>9}  RESULT", "SLA")?;
+    }
+    writeln!(w)?;
+
+    for u in checks {
+        let name = truncate_name(&display_name(u), options.name_width);
+        let percentage = match (u.percentage, u.sla_met) {
+            (Some(percentage), Some(met)) => colored_percentage_cell(percentage, if met { Color::Green } else { Color::Red }),
+            (Some(percentage), None) => colored_percentage_cell(percentage, color::for_percentage(percentage)),
+            (None, _) => format!("{:>9}", "n/a"),
+        };
+        write!(w, "{:<name_width$}  {}  {:>9}", name, percentage, format!("{} mins", u.downtime_mins))?;
+        if layout.with_outages {
+            write!(w, "  {:>7}", u.outages.map(|o| o.to_string()).unwrap_or_default())?;
+        }
+        if let (Some(sla), Some(met)) = (u.sla, u.sla_met) {
+            write!(w, "  {:>9}  {}", format!("{}%", sla), if met { "PASS" } else { "FAIL" })?;
+        }
+        writeln!(w)?;
+        write_regions(w, u)?;
+    }
+
+    Ok(())
+}
+
+/// The fleet-wide summary lines that end the text report.
+pub fn write_text_footer<W: Write>(w: &mut W, report: &Report, options: &RenderOptions) -> io::Result<()> {
+    let aggregate = &report.aggregate;
+    if let Some(percentage) = aggregate.percentage {
+        writeln!(w)?;
+        write!(w, "All checks, {}, {} mins", colored_percentage(percentage), aggregate.downtime_mins)?;
+        match aggregate.raw_percentage {
+            Some(raw) => writeln!(w, " (raw {}% before maintenance)", raw)?,
+            None => writeln!(w)?,
+        }
+    }
+    if let Some(worst) = &aggregate.worst_check {
+        writeln!(w, "Worst check, {}, {}", worst.name, colored_percentage(worst.percentage))?;
+    }
+    if report.checks.iter().any(|c| c.sla.is_some()) {
+        writeln!(w, "SLA failures, {}", aggregate.sla_failures)?;
+    }
+    if report.checks.iter().any(|c| c.alerting.is_some()) {
+        writeln!(w, "Checks without alerting, {}", aggregate.without_alerting)?;
+    }
+    if let Some(cost) = aggregate.downtime_cost {
+        writeln!(w, "Estimated downtime cost, {}{:.2}", options.currency_symbol, cost)?;
+    }
+    if let Some(statistics) = &aggregate.statistics {
+        let median = statistics.median.map(colored_percentage).unwrap_or_else(|| "n/a".to_string());
+        let stddev = statistics.stddev.map(|s| s.to_string()).unwrap_or_else(|| "n/a".to_string());
+        writeln!(w, "Median, {}, stddev {}", median, stddev)?;
+        let nines = statistics.nines;
+        writeln!(
+            w,
+            "Nines, five {}, four {}, three {}, two {}, below {}",
+            nines.five_nines, nines.four_nines, nines.three_nines, nines.two_nines, nines.below_two_nines
+        )?;
+    }
+
+    Ok(())
+}
+
+/// One row of `--group-summary-only`: a tag's checks combined, or every check for the total.
+#[derive(Serialize)]
+struct GroupSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    check_count: usize,
+    percentage: Option<f64>,
+    downtime_minutes: u64,
+}
+
+impl GroupSummary {
+    fn new(tag: Option<String>, aggregate: &Aggregate) -> Self {
+        GroupSummary {
+            tag,
+            check_count: aggregate.check_count,
+            percentage: aggregate.percentage,
+            downtime_minutes: aggregate.downtime_mins,
+        }
+    }
+}
+
+/// Writes only the `--group-by-tag` subtotals, one row per tag, and a grand total over every
+/// check (counted once, however many tags it has): text lines, one JSON document with `groups`
+/// and `total`, or CSV/TSV rows with the total last, under the tag `(all checks)`.
+pub fn write_group_summary<W: Write>(w: &mut W, report: &Report, format: OutputFormat, pretty: bool, options: &RenderOptions) -> io::Result<()> {
+    let groups: Vec<GroupSummary> = report::group_by_tag(&report.checks)
+        .into_iter()
+        .map(|(tag, checks)| GroupSummary::new(Some(tag), &Aggregate::from_checks(&checks)))
+        .collect();
+    let total = GroupSummary::new(None, &report.aggregate);
+
+    match format {
+        OutputFormat::Json => {
+            #[derive(Serialize)]
+            struct GroupSummaryDocument {
+                from: DateTime<Utc>,
+                to: DateTime<Utc>,
+                groups: Vec<GroupSummary>,
+                total: GroupSummary,
+            }
+            write_json_value(w, &GroupSummaryDocument { from: report.from, to: report.to, groups, total }, pretty)
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let mut writer = delimited_writer(w, format, options);
+            if options.header {
+                let names = ["tag", "check_count", "percentage", "downtime_minutes"];
+                writer.write_record(delimited_record(format, names.iter().map(|n| n.to_string()).collect()))?;
+            }
+            for group in groups.iter().chain([&total]) {
+                let values = vec![
+                    group.tag.clone().unwrap_or_else(|| "(all checks)".to_string()),
+                    group.check_count.to_string(),
+                    group.percentage.map(|p| p.to_string()).unwrap_or_default(),
+                    group.downtime_minutes.to_string(),
+                ];
+                writer.write_record(delimited_record(format, values))?;
+            }
+            writer.flush()
+        }
+        _ => {
+            let line = |name: &str, group: &GroupSummary| {
+                let percentage = group.percentage.map(colored_percentage).unwrap_or_else(|| "no data".to_string());
+                format!("{}, {}, {} mins, {} checks", name, percentage, group.downtime_minutes, group.check_count)
+            };
+            for group in &groups {
+                writeln!(w, "{}", line(group.tag.as_deref().unwrap_or_default(), group))?;
+            }
+            writeln!(w)?;
+            writeln!(w, "{}", line("All checks", &total))
+        }
+    }
+}
+
+/// Writes only the fleet-wide aggregate: the text footer, the `aggregate` object for JSON and
+/// ndjson, or one header (unless `--no-header`) and one data row for CSV/TSV.
+pub fn write_summary<W: Write>(w: &mut W, report: &Report, format: OutputFormat, pretty: bool, options: &RenderOptions) -> io::Result<()> {
+    let aggregate = &report.aggregate;
+    match format {
+        OutputFormat::Json => write_json_value(w, aggregate, pretty),
+        OutputFormat::Ndjson => write_json_value(w, aggregate, false),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let names = ["check_count", "percentage", "downtime_minutes", "worst_check", "worst_percentage", "sla_failures", "without_alerting"];
+            let mut writer = delimited_writer(w, format, options);
+            if options.header {
+                writer.write_record(delimited_record(format, names.iter().map(|n| n.to_string()).collect()))?;
+            }
+            let worst = aggregate.worst_check.as_ref();
+            let values = vec![
+                aggregate.check_count.to_string(),
+                aggregate.percentage.map(|p| p.to_string()).unwrap_or_default(),
+                aggregate.downtime_mins.to_string(),
+                worst.map(|c| c.name.clone()).unwrap_or_default(),
+                worst.map(|c| c.percentage.to_string()).unwrap_or_default(),
+                aggregate.sla_failures.to_string(),
+                aggregate.without_alerting.to_string(),
+            ];
+            writer.write_record(delimited_record(format, values))?;
+            writer.flush()
+        }
+        _ => write_text_footer(w, report, options),
+    }
+}
+
+/// Writes the `--probe-breakdown` of one check: a line per probe in text, or an object with the
+/// check name and the probes in JSON.
+pub fn write_probe_breakdown<W: Write>(
+    w: &mut W,
+    name: &str,
+    probes: &[ProbeUptime],
+    format: OutputFormat,
+    pretty: bool,
+) -> io::Result<()> {
+    #[derive(Serialize)]
+    struct ProbeBreakdown<'a> {
+        check: &'a str,
+        probes: &'a [ProbeUptime],
+    }
+    if format == OutputFormat::Json {
+        return write_json_value(w, &ProbeBreakdown { check: name, probes }, pretty);
+    }
+    if probes.is_empty() {
+        return writeln!(w, "No probe-level data for {} in this window", name);
+    }
+    writeln!(w, "Probes for {}, most downtime first", name)?;
+    for p in probes {
+        let probe = p.name.clone().unwrap_or_else(|| format!("probe {}", p.probe_id));
+        let region = p.region.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default();
+        writeln!(w, "{}{}, {}, {} mins", probe, region, percentage_or_na(p.percentage), p.downtime_mins)?;
+    }
+    Ok(())
+}
+
+/// The `--probe-from` spot check: the check's figures over the window, then one line per hour.
+pub fn write_spot_check<W: Write>(w: &mut W, u: &UptimeResult, from: DateTime<Utc>, to: DateTime<Utc>, format: OutputFormat, pretty: bool) -> io::Result<()> {
+    if format == OutputFormat::Json {
+        return write_json_value(w, u, pretty);
+    }
+    let Some(percentage) = u.percentage else {
+        return writeln!(w, "No data for {} ({}) from {} to {}", display_name(u), u.id, from.to_rfc3339(), to.to_rfc3339());
+    };
+    writeln!(
+        w,
+        "{} ({}) from {} to {}: {}, {} mins down, {}s unmonitored",
+        display_name(u),
+        u.id,
+        from.to_rfc3339(),
+        to.to_rfc3339(),
+        colored_percentage(percentage),
+        u.downtime_mins,
+        u.unmonitored
+    )?;
+    for bucket in u.breakdown.iter().flatten() {
+        match bucket.percentage {
+            Some(percentage) => writeln!(w, "  {}  {}, {}s down", bucket.start.to_rfc3339(), colored_percentage(percentage), bucket.downtime)?,
+            None => writeln!(w, "  {}  not monitored", bucket.start.to_rfc3339())?,
+        }
+    }
+    Ok(())
+}
+
+/// TSV has no quoting, so tabs and line breaks inside values become spaces.
+fn tsv_escape(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+/// A `csv::Writer` for CSV (quoting values that contain the delimiter, a quote or a newline) or
+/// TSV (no quoting). Lines end in `\n`; `--line-ending crlf` is applied by the sink.
+fn delimited_writer<W: Write>(w: W, format: OutputFormat, options: &RenderOptions) -> csv::Writer<W> {
+    let mut builder = csv::WriterBuilder::new();
+    builder.terminator(csv::Terminator::Any(b'\n'));
+    match format {
+        OutputFormat::Tsv => builder.delimiter(b'\t').quote_style(csv::QuoteStyle::Never),
+        // --delimiter is checked to be ASCII up front
+        _ => builder.delimiter(options.delimiter.map_or(b',', |d| d as u8)),
+    };
+    builder.from_writer(w)
+}
+
+fn delimited_record(format: OutputFormat, values: Vec<String>) -> Vec<String> {
+    match format {
+        OutputFormat::Tsv => values.iter().map(|v| tsv_escape(v)).collect(),
+        _ => values,
+    }
+}
+
+/// Names the check whose row could not be written, keeping the kind of the error.
+fn row_error(u: &UptimeResult, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("could not write {} ({}): {}", u.name, u.id, e))
+}
+
+fn selected_fields(fields: &[Field]) -> &[Field] {
+    if fields.is_empty() { DEFAULT_FIELDS } else { fields }
+}
+
+fn write_delimited_header<W: Write>(w: &mut csv::Writer<W>, format: OutputFormat, options: &RenderOptions) -> io::Result<()> {
+    if !options.header {
+        return Ok(());
+    }
+    let names = selected_fields(&options.fields).iter().map(|f| f.output_name(&options.renames)).collect();
+    Ok(w.write_record(delimited_record(format, names))?)
+}
+
+fn write_delimited_row<W: Write>(w: &mut csv::Writer<W>, format: OutputFormat, u: &UptimeResult, options: &RenderOptions) -> io::Result<()> {
+    let values = selected_fields(&options.fields).iter().map(|f| f.value(u)).collect();
+    Ok(w.write_record(delimited_record(format, values))?)
+}
+
+pub fn write_delimited<W: Write>(w: &mut W, report: &Report, format: OutputFormat, options: &RenderOptions) -> io::Result<()> {
+    let mut writer = delimited_writer(w, format, options);
+    write_delimited_header(&mut writer, format, options)?;
+    for u in &report.checks {
+        write_delimited_row(&mut writer, format, u, options)
+            .map_err(|e| row_error(u, e))?;
+    }
+    writer.flush()
+}
+
+/// Writes the `--aggregate-by` series pivoted: the usual columns followed by the availability
+/// of the check in each period, one column per period named after its start date. Periods in
+/// which a check has no data are left empty.
+pub fn write_series_delimited<W: Write>(
+    w: &mut W,
+    report: &Report,
+    periods: &[NaiveDate],
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    let fields = selected_fields(&options.fields);
+    let mut writer = delimited_writer(w, format, options);
+    if options.header {
+        let names = fields
+            .iter()
+            .map(|f| f.output_name(&options.renames))
+            .chain(periods.iter().map(|p| p.to_string()))
+            .collect();
+        writer.write_record(delimited_record(format, names))?;
+    }
+    for u in &report.checks {
+        let series = u.series.as_deref().unwrap_or_default();
+        let values = fields
+            .iter()
+            .map(|f| f.value(u))
+            .chain(periods.iter().map(|p| {
+                series.iter().find(|s| s.start == *p).and_then(|s| s.percentage).map(|p| p.to_string()).unwrap_or_default()
+            }))
+            .collect();
+        writer
+            .write_record(delimited_record(format, values))
+            .map_err(|e| row_error(u, e.into()))?;
+    }
+    writer.flush()
+}
+
+/// Writes the `--aggregate-by` series the other way round from `write_series_delimited`: one
+/// row per period, starting with its start date, and one column of availability per check,
+/// named after the check (with its ID when two checks share a name). Cells of periods in which
+/// a check has no data, such as those before it was created, are left empty.
+pub fn write_series_wide<W: Write>(w: &mut W, report: &Report, periods: &[NaiveDate], options: &RenderOptions) -> io::Result<()> {
+    let mut writer = delimited_writer(w, OutputFormat::Csv, options);
+    if options.header {
+        let names: Vec<String> = report.checks.iter().map(display_name).collect();
+        let columns = report
+            .checks
+            .iter()
+            .zip(&names)
+            .map(|(u, name)| match names.iter().filter(|n| *n == name).count() {
+                1 => name.clone(),
+                _ => format!("{} ({})", name, u.id),
+            });
+        writer.write_record(std::iter::once("period".to_string()).chain(columns))?;
+    }
+    for period in periods {
+        let cells = report.checks.iter().map(|u| {
+            let series = u.series.as_deref().unwrap_or_default();
+            series.iter().find(|s| s.start == *period).and_then(|s| s.percentage).map(|p| p.to_string()).unwrap_or_default()
+        });
+        writer.write_record(std::iter::once(period.to_string()).chain(cells))?;
+    }
+    writer.flush()
+}
+
+fn write_ndjson_row<W: Write>(w: &mut W, u: &UptimeResult, renames: &[(Field, String)]) -> io::Result<()> {
+    if renames.is_empty() {
+        serde_json::to_writer(&mut *w, u)?;
+    } else {
+        let mut check = serde_json::to_value(u)?;
+        fields::rename_keys(&mut check, renames);
+        serde_json::to_writer(&mut *w, &check)?;
+    }
+    writeln!(w)
+}
+
+pub fn write_ndjson<W: Write>(w: &mut W, report: &Report, renames: &[(Field, String)]) -> io::Result<()> {
+    for u in &report.checks {
+        write_ndjson_row(w, u, renames)?;
+    }
+    Ok(())
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Writes one `<testcase>` per check. A check fails when it is below its SLA, or below
+/// `fail_under` when it has no SLA of its own. A check that was not monitored at all in the
+/// window cannot pass or fail, so it is skipped.
+pub fn write_junit<W: Write>(w: &mut W, report: &Report, fail_under: Option<f64>) -> io::Result<()> {
+    let threshold = |u: &UptimeResult| u.sla.or(fail_under);
+    let failures = report
+        .checks
+        .iter()
+        .filter(|u| threshold(u).zip(u.percentage).is_some_and(|(t, percentage)| percentage < t))
+        .count();
+    let skipped = report.checks.iter().filter(|u| u.percentage.is_none()).count();
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<testsuite name="pingdom-uptime" tests="{}" failures="{}" errors="0" skipped="{}" timestamp="{}">"#,
+        report.checks.len(),
+        failures,
+        skipped,
+        report.generated_at.format("%Y-%m-%dT%H:%M:%S")
+    )?;
+    for u in &report.checks {
+        let name = xml_escape(&u.name);
+        match (threshold(u), u.percentage) {
+            (_, None) => {
+                writeln!(w, r#"  <testcase classname="pingdom" name="{}" time="0">"#, name)?;
+                writeln!(
+                    w,
+                    r#"    <skipped message="not monitored between {} and {}"/>"#,
+                    report.from.to_rfc3339(),
+                    report.to.to_rfc3339()
+                )?;
+                writeln!(w, "  </testcase>")?;
+            }
+            (Some(t), Some(percentage)) if percentage < t => {
+                writeln!(w, r#"  <testcase classname="pingdom" name="{}" time="0">"#, name)?;
+                writeln!(
+                    w,
+                    r#"    <failure message="{}% is below the SLA of {}%">{} mins of downtime between {} and {}</failure>"#,
+                    percentage,
+                    t,
+                    u.downtime_mins,
+                    report.from.to_rfc3339(),
+                    report.to.to_rfc3339()
+                )?;
+                writeln!(w, "  </testcase>")?;
+            }
+            _ => writeln!(w, r#"  <testcase classname="pingdom" name="{}" time="0"/>"#, name)?,
+        }
+    }
+    writeln!(w, "</testsuite>")
+}
+
+/// Escapes an InfluxDB line-protocol tag value: commas, equals signs and spaces get a backslash.
+/// This is not the escaping of CSV or Prometheus labels, and quotes stay as they are. A tag
+/// value cannot hold a line break at all, so those become (escaped) spaces.
+fn influx_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push_str("\\ "),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes one `pingdom` point per check, tagged with its name, ID and account, for piping into
+/// `influx write`. Every point carries the end of the window as its timestamp, in nanoseconds.
+pub fn write_influx<W: Write>(w: &mut W, report: &Report) -> io::Result<()> {
+    let timestamp = report.to.timestamp_nanos_opt().unwrap_or_default();
+    for u in &report.checks {
+        let mut tags = String::new();
+        if let Some(account) = u.account.as_deref().filter(|a| !a.is_empty()) {
+            tags.push_str(&format!(",account={}", influx_tag(account)));
+        }
+        // Tag values cannot be empty, so a check without a name is only tagged with its ID
+        if !u.name.is_empty() {
+            tags.push_str(&format!(",check={}", influx_tag(&u.name)));
+        }
+        tags.push_str(&format!(",id={}", u.id));
+
+        let mut fields = Vec::new();
+        // Without uptime data there is no ratio to write; the percentage has four decimals, so
+        // the ratio is exact with six
+        if let Some(percentage) = u.percentage {
+            fields.push(format!("uptime_ratio={}", (percentage * 10000.0).round() / 1_000_000.0));
+        }
+        fields.push(format!("downtime_minutes={}", u.downtime_mins));
+        writeln!(w, "pingdom{} {} {}", tags, fields.join(","), timestamp)?;
+    }
+    Ok(())
+}
+
+/// Name the `--output-template` is registered under.
+const TEMPLATE_NAME: &str = "report";
+
+/// Reads and compiles an `--output-template` up front, so a syntax error stops the run before
+/// any request is made. Values are inserted as they are, without Handlebars' HTML escaping,
+/// since the output is plain text.
+pub fn load_template(path: &Path) -> Result<Handlebars<'static>, Box<dyn Error>> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("could not read template {}: {}", path.display(), e))?;
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .register_template_string(TEMPLATE_NAME, source)
+        .map_err(|e| format!("invalid template {}: {}", path.display(), e))?;
+    Ok(handlebars)
+}
+
+/// Renders the whole report through the template once, with the `--format json` document as
+/// the context.
+pub fn write_template<W: Write>(w: &mut W, template: &Handlebars, report: &Report) -> Result<(), Box<dyn Error>> {
+    template
+        .render_to_write(TEMPLATE_NAME, report, w)
+        .map_err(|e| format!("could not render the template: {}", e))?;
+    Ok(())
+}
+
+pub fn write_json<W: Write>(w: &mut W, report: &Report, pretty: bool, renames: &[(Field, String)]) -> io::Result<()> {
+    if renames.is_empty() {
+        write_json_value(w, report, pretty)
+    } else {
+        // Renames apply to the per-check objects, not to the aggregate
+        let mut document = serde_json::to_value(report)?;
+        for check in document["checks"].as_array_mut().into_iter().flatten() {
+            fields::rename_keys(check, renames);
+        }
+        write_json_value(w, &document, pretty)
+    }
+}
+
+fn write_json_value<W: Write, T: serde::Serialize>(w: &mut W, value: &T, pretty: bool) -> io::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(&mut *w, value)?;
+    } else {
+        serde_json::to_writer(&mut *w, value)?;
+    }
+    writeln!(w)
+}
+
+/// Escapes a Prometheus label value (backslash, double quote and newline).
+fn prometheus_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn write_prometheus<W: Write>(w: &mut W, report: &Report, run_at: DateTime<Utc>) -> io::Result<()> {
+    writeln!(w, "# HELP pingdom_last_run_timestamp Unix time of the last completed report run")?;
+    writeln!(w, "# TYPE pingdom_last_run_timestamp gauge")?;
+    writeln!(w, "pingdom_last_run_timestamp {}", run_at.timestamp())?;
+
+    writeln!(w, "# HELP pingdom_check_uptime_percentage Availability of the check over the report window")?;
+    writeln!(w, "# TYPE pingdom_check_uptime_percentage gauge")?;
+    // Checks without data have no availability to report
+    for u in &report.checks {
+        if let Some(percentage) = u.percentage {
+            writeln!(w, "pingdom_check_uptime_percentage{{id=\"{}\",name=\"{}\"}} {}", u.id, prometheus_label(&u.name), percentage)?;
+        }
+    }
+
+    writeln!(w, "# HELP pingdom_check_downtime_minutes Downtime of the check over the report window")?;
+    writeln!(w, "# TYPE pingdom_check_downtime_minutes gauge")?;
+    for u in &report.checks {
+        writeln!(w, "pingdom_check_downtime_minutes{{id=\"{}\",name=\"{}\"}} {}", u.id, prometheus_label(&u.name), u.downtime_mins)?;
+    }
+
+    if let Some(percentage) = report.aggregate.percentage {
+        writeln!(w, "# HELP pingdom_uptime_percentage Availability across all checks")?;
+        writeln!(w, "# TYPE pingdom_uptime_percentage gauge")?;
+        writeln!(w, "pingdom_uptime_percentage {}", percentage)?;
+    }
+
+    Ok(())
+}
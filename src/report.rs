@@ -0,0 +1,634 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Uptime figures for a single check over the report window. All durations are in seconds
+/// unless the field name says otherwise.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct UptimeResult {
+    pub id: u64,
+    /// Account the check belongs to, present in multi-account runs (`--account`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    pub name: String,
+    /// Hostname, or URL or host:port when known, that the check monitors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub uptime: u64,
+    pub downtime: u64,
+    pub unmonitored: u64,
+    pub max_uptime: u64,
+    /// Availability over the window; `None` when nothing was monitored
+    pub percentage: Option<f64>,
+    #[serde(rename = "downtime_minutes", alias = "downtime_mins")]
+    pub downtime_mins: u64,
+    /// Mean of the per-bucket average response times
+    #[serde(default)]
+    pub avg_response_ms: Option<u64>,
+    /// Number of down periods, present with `--with-outages`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outages: Option<usize>,
+    /// The down periods by length, present with `--with-outages`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outage_distribution: Option<OutageDistribution>,
+    /// Figures per `--resolution` bucket, present with `--with-weekly-breakdown`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<Vec<BucketUptime>>,
+    /// Availability per probe region, present with `--by-region`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regions: Option<BTreeMap<String, f64>>,
+    /// SLA threshold this check was evaluated against, present with `--sla`/`--sla-per-tag`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sla: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sla_met: Option<bool>,
+    /// Downtime the SLA allows over the window, present with `--error-budget`
+    #[serde(rename = "error_budget_minutes", alias = "error_budget_mins", default, skip_serializing_if = "Option::is_none")]
+    pub error_budget_mins: Option<f64>,
+    /// What is left of the error budget after the actual downtime; negative once overspent
+    #[serde(rename = "error_budget_remaining_minutes", alias = "error_budget_remaining_mins", default, skip_serializing_if = "Option::is_none")]
+    pub error_budget_remaining_mins: Option<f64>,
+    /// True when nothing is left of the error budget
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_budget_exhausted: Option<bool>,
+    /// Estimated cost of the downtime, present with `--cost-per-minute`/`--cost-per-tag`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downtime_cost: Option<f64>,
+    /// When the most recent down period started, from the checks list
+    #[serde(default)]
+    pub last_down: Option<DateTime<FixedOffset>>,
+    /// When the most recent down period ended
+    #[serde(default)]
+    pub last_up: Option<DateTime<FixedOffset>>,
+    /// When the check last ran a test
+    #[serde(default)]
+    pub last_test: Option<DateTime<FixedOffset>>,
+    /// When the check was created, from the checks list
+    #[serde(default)]
+    pub created: Option<DateTime<FixedOffset>>,
+    /// True when the check was created after the window started, so the percentage only covers
+    /// part of the window
+    #[serde(default)]
+    pub created_in_window: bool,
+    /// True when Pingdom's performance summary lacked the buckets or some of their totals, which
+    /// were counted as 0
+    #[serde(default)]
+    pub incomplete_data: bool,
+    /// True when fewer tests than `--probe-count-min` are estimated to back the percentage,
+    /// present with that flag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_confidence: Option<bool>,
+    /// Seconds of downtime inside `--maintenance` windows, left out of `downtime` and `max_uptime`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_downtime: Option<u64>,
+    /// Availability before the `--maintenance` windows were left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_percentage: Option<f64>,
+    /// Seconds of downtime outside Pingdom's confirmed down states, counted as uptime with
+    /// `--exclude-unconfirmed`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unconfirmed_downtime: Option<u64>,
+    /// Number of integrations attached to the check, present with `--with-alerting`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrations: Option<usize>,
+    /// Number of users and teams alerted by the check, present with `--with-alerting`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert_contacts: Option<usize>,
+    /// Whether anything is notified when the check goes down, present with `--with-alerting`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerting: Option<bool>,
+    /// Average response time per day, present with `--response-trend`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_trend: Option<Vec<TrendPoint>>,
+    /// Availability per day, week or month of the window, present with `--aggregate-by`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series: Option<Vec<PeriodUptime>>,
+}
+
+/// One bucket (a week by default) of a check's performance summary.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct BucketUptime {
+    pub start: DateTime<Utc>,
+    pub uptime: u64,
+    pub downtime: u64,
+    pub unmonitored: u64,
+    /// `None` when nothing was monitored in the bucket
+    pub percentage: Option<f64>,
+    /// Whether the bucket is far enough below the check's percentage to be an incident
+    /// candidate, present with `--flag-anomalies`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anomaly: Option<bool>,
+}
+
+/// Marks the buckets whose availability is more than `threshold` percentage points below the
+/// check's percentage over the whole window. Buckets without monitoring are never flagged.
+pub fn flag_anomalies(result: &mut UptimeResult, threshold: f64) {
+    let overall = result.percentage;
+    for bucket in result.breakdown.iter_mut().flatten() {
+        bucket.anomaly = Some(matches!((overall, bucket.percentage), (Some(overall), Some(p)) if overall - p > threshold));
+    }
+}
+
+/// How many down periods were short blips and how many were long outages.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default)]
+pub struct OutageDistribution {
+    /// Shorter than 5 minutes
+    #[serde(rename = "under_5_minutes", alias = "under_5_mins")]
+    pub under_5_mins: usize,
+    /// From 5 up to and including 30 minutes
+    #[serde(rename = "from_5_to_30_minutes", alias = "from_5_to_30_mins")]
+    pub from_5_to_30_mins: usize,
+    /// Longer than 30 minutes
+    #[serde(rename = "over_30_minutes", alias = "over_30_mins")]
+    pub over_30_mins: usize,
+}
+
+impl OutageDistribution {
+    pub fn from_durations(durations: &[u64]) -> Self {
+        let mut distribution = OutageDistribution::default();
+        for &seconds in durations {
+            match seconds {
+                0..300 => distribution.under_5_mins += 1,
+                300..=1800 => distribution.from_5_to_30_mins += 1,
+                _ => distribution.over_30_mins += 1,
+            }
+        }
+        distribution
+    }
+}
+
+/// What one probe server saw of a check over the window, for `--probe-breakdown`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbeUptime {
+    pub probe_id: u64,
+    /// Probe name and region from `/probes`, when Pingdom still lists the probe
+    pub name: Option<String>,
+    pub region: Option<String>,
+    pub uptime: u64,
+    pub downtime: u64,
+    pub unmonitored: u64,
+    pub percentage: Option<f64>,
+    #[serde(rename = "downtime_minutes", alias = "downtime_mins")]
+    pub downtime_mins: u64,
+}
+
+/// A check's availability over one `--aggregate-by` period, starting on `start` in `--timezone`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct PeriodUptime {
+    pub start: NaiveDate,
+    pub uptime: u64,
+    pub downtime: u64,
+    pub unmonitored: u64,
+    pub percentage: Option<f64>,
+    #[serde(rename = "downtime_minutes", alias = "downtime_mins")]
+    pub downtime_mins: u64,
+}
+
+/// One day of a check's response time trend.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct TrendPoint {
+    pub date: NaiveDate,
+    pub avg_response_ms: u64,
+}
+
+/// Fleet-wide figures computed over every check in the report.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Aggregate {
+    pub check_count: usize,
+    /// Availability across all checks combined; `None` when nothing was monitored
+    pub percentage: Option<f64>,
+    #[serde(rename = "downtime_minutes", alias = "downtime_mins")]
+    pub downtime_mins: u64,
+    pub worst_check: Option<WorstCheck>,
+    /// Number of checks below their SLA threshold
+    #[serde(default)]
+    pub sla_failures: usize,
+    /// Number of checks known to have no alerting configured
+    #[serde(default)]
+    pub without_alerting: usize,
+    /// Estimated cost of all checks' downtime, present when any check has a cost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downtime_cost: Option<f64>,
+    /// Availability across all checks before the `--maintenance` windows were left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_percentage: Option<f64>,
+    /// How availability is spread across the checks, present with `--summary-statistics`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<Statistics>,
+}
+
+/// The distribution of availability across checks, for `--summary-statistics`. Checks without
+/// an availability figure (no uptime data) are left out.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Statistics {
+    /// Median availability; `None` without any check
+    pub median: Option<f64>,
+    /// Sample standard deviation of availability; `None` with fewer than two checks
+    pub stddev: Option<f64>,
+    pub nines: NinesTiers,
+}
+
+/// Number of checks at each level of availability; every check counts in its highest tier only.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default)]
+pub struct NinesTiers {
+    /// 99.999% or better
+    pub five_nines: usize,
+    /// From 99.99% up to 99.999%
+    pub four_nines: usize,
+    /// From 99.9% up to 99.99%
+    pub three_nines: usize,
+    /// From 99% up to 99.9%
+    pub two_nines: usize,
+    /// Below 99%
+    pub below_two_nines: usize,
+}
+
+impl Statistics {
+    pub fn from_checks(checks: &[UptimeResult]) -> Self {
+        let mut percentages: Vec<f64> = checks.iter().filter_map(|c| c.percentage).collect();
+        percentages.sort_by(f64::total_cmp);
+        let n = percentages.len();
+
+        let median = match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(percentages[n / 2]),
+            _ => Some(round4((percentages[n / 2 - 1] + percentages[n / 2]) / 2.0)),
+        };
+        let stddev = (n >= 2).then(|| {
+            let mean = percentages.iter().sum::<f64>() / n as f64;
+            let variance = percentages.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            round4(variance.sqrt())
+        });
+
+        let mut nines = NinesTiers::default();
+        for &p in &percentages {
+            match p {
+                p if p >= 99.999 => nines.five_nines += 1,
+                p if p >= 99.99 => nines.four_nines += 1,
+                p if p >= 99.9 => nines.three_nines += 1,
+                p if p >= 99.0 => nines.two_nines += 1,
+                _ => nines.below_two_nines += 1,
+            }
+        }
+
+        Statistics { median, stddev, nines }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct WorstCheck {
+    pub id: u64,
+    pub name: String,
+    pub percentage: f64,
+}
+
+/// A complete uptime report. This is the documented JSON schema: it is what `--format json`
+/// writes and what saved reports are read back as.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Report {
+    pub generated_at: DateTime<Utc>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub checks: Vec<UptimeResult>,
+    pub aggregate: Aggregate,
+}
+
+impl Report {
+    pub fn new(from: DateTime<Utc>, to: DateTime<Utc>, checks: Vec<UptimeResult>) -> Self {
+        let aggregate = Aggregate::from_checks(&checks);
+        Report {
+            generated_at: Utc::now(),
+            from,
+            to,
+            checks,
+            aggregate,
+        }
+    }
+}
+
+impl Report {
+    /// Reads a report previously written with `--format json`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path).map_err(|e| format!("could not open report {}: {}", path.display(), e))?;
+        let report = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("could not parse report {}: {}", path.display(), e))?;
+        Ok(report)
+    }
+}
+
+impl Aggregate {
+    pub fn from_checks(checks: &[UptimeResult]) -> Self {
+        let uptime = checks.iter().map(|c| c.uptime).sum();
+        let unmonitored = checks.iter().map(|c| c.unmonitored).sum();
+        let max_uptime: u64 = checks.iter().map(|c| c.max_uptime).sum();
+        let maintenance_downtime = checks.iter().filter_map(|c| c.maintenance_downtime).reduce(|a, b| a + b);
+
+        // Checks arrive in completion order, so ties are broken by name, ID and account to always
+        // name the same worst check. Checks without data have no percentage to compare.
+        let worst_check = checks
+            .iter()
+            .filter_map(|c| Some((c, c.percentage?)))
+            .min_by(|(a, a_percentage), (b, b_percentage)| {
+                a_percentage
+                    .total_cmp(b_percentage)
+                    .then_with(|| (&a.name, a.id, &a.account).cmp(&(&b.name, b.id, &b.account)))
+            })
+            .map(|(c, percentage)| WorstCheck {
+                id: c.id,
+                name: c.name.clone(),
+                percentage,
+            });
+
+        Aggregate {
+            check_count: checks.len(),
+            percentage: uptime_percentage(uptime, unmonitored, max_uptime),
+            downtime_mins: checks.iter().map(|c| c.downtime_mins).sum(),
+            worst_check,
+            sla_failures: checks.iter().filter(|c| c.sla_met == Some(false)).count(),
+            without_alerting: checks.iter().filter(|c| c.alerting == Some(false)).count(),
+            downtime_cost: checks.iter().filter_map(|c| c.downtime_cost).reduce(|a, b| a + b).map(round_cents),
+            raw_percentage: maintenance_downtime
+                .filter(|_| max_uptime > 0)
+                .and_then(|excluded| uptime_percentage(uptime, unmonitored, max_uptime + excluded)),
+            statistics: None,
+        }
+    }
+}
+
+/// Checks grouped by tag, in tag order. A check with several tags appears in each of their
+/// groups; checks without tags are grouped last under `(untagged)`.
+pub fn group_by_tag(checks: &[UptimeResult]) -> Vec<(String, Vec<UptimeResult>)> {
+    let mut groups: BTreeMap<&str, Vec<UptimeResult>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for check in checks {
+        if check.tags.is_empty() {
+            untagged.push(check.clone());
+        }
+        for tag in &check.tags {
+            groups.entry(tag).or_default().push(check.clone());
+        }
+    }
+
+    let mut groups: Vec<(String, Vec<UptimeResult>)> = groups.into_iter().map(|(tag, checks)| (tag.to_string(), checks)).collect();
+    if !untagged.is_empty() {
+        groups.push(("(untagged)".to_string(), untagged));
+    }
+    groups
+}
+
+/// SLA thresholds from `--sla` and `--sla-per-tag`.
+#[derive(Clone, Debug, Default)]
+pub struct SlaPolicy {
+    pub default: Option<f64>,
+    pub per_tag: Vec<(String, f64)>,
+    /// Also work out the error budget, for `--error-budget`
+    pub error_budget: bool,
+}
+
+impl SlaPolicy {
+    /// The threshold for a check with the given tags. When several tags have thresholds the
+    /// strictest one applies; checks without a matching tag fall back to the global SLA.
+    pub fn threshold_for(&self, tags: &[String]) -> Option<f64> {
+        self.per_tag
+            .iter()
+            .filter(|(tag, _)| tags.contains(tag))
+            .map(|(_, threshold)| *threshold)
+            .reduce(f64::max)
+            .or(self.default)
+    }
+
+    pub fn apply(&self, result: &mut UptimeResult) {
+        result.sla = self.threshold_for(&result.tags);
+        // Without data there is nothing to hold against the SLA, so a check without a percentage
+        // neither meets nor misses it
+        result.sla_met = result.sla.zip(result.percentage).map(|(threshold, percentage)| percentage >= threshold);
+        // The budget is the share of the window the SLA lets the check be down, e.g. 43.2
+        // minutes for 99.9% of 30 days
+        let budget = result.sla.filter(|_| self.error_budget).map(|threshold| {
+            let budget_mins = result.max_uptime as f64 / 60.0 * (100.0 - threshold) / 100.0;
+            (budget_mins, budget_mins - result.downtime as f64 / 60.0)
+        });
+        result.error_budget_mins = budget.map(|(budget, _)| round_tenths(budget));
+        result.error_budget_remaining_mins = budget.map(|(_, remaining)| round_tenths(remaining));
+        result.error_budget_exhausted = budget.map(|(_, remaining)| remaining <= 0.0);
+    }
+}
+
+/// Downtime cost rates from `--cost-per-minute` and `--cost-per-tag`.
+#[derive(Clone, Debug, Default)]
+pub struct CostPolicy {
+    pub default: Option<f64>,
+    pub per_tag: Vec<(String, f64)>,
+}
+
+impl CostPolicy {
+    /// The cost of a minute of downtime for a check with the given tags. When several tags have
+    /// a rate the highest one applies; checks without a matching tag use `--cost-per-minute`.
+    pub fn rate_for(&self, tags: &[String]) -> Option<f64> {
+        self.per_tag
+            .iter()
+            .filter(|(tag, _)| tags.contains(tag))
+            .map(|(_, rate)| *rate)
+            .reduce(f64::max)
+            .or(self.default)
+    }
+
+    pub fn apply(&self, result: &mut UptimeResult) {
+        result.downtime_cost = self.rate_for(&result.tags).map(|rate| round_cents(rate * result.downtime_mins as f64));
+    }
+}
+
+fn round_tenths(minutes: f64) -> f64 {
+    (minutes * 10.0).round() / 10.0
+}
+
+/// Rounds to the four decimals percentages are reported with, so figures derived from them
+/// (deltas, medians, ...) don't show float noise.
+pub(crate) fn round4(value: f64) -> f64 {
+    (value * 10000.0).round() / 10000.0
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Order of the checks in the report.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// Check name, A to Z
+    Name,
+    /// Lowest availability first
+    Percentage,
+    /// Most downtime first
+    Downtime,
+    /// Check ID, ascending
+    Id,
+}
+
+impl SortKey {
+    /// Sorts in place; ties are broken by name, account and ID so the order is always the same.
+    pub fn sort(self, checks: &mut [UptimeResult]) {
+        checks.sort_by(|a, b| {
+            let by_key = match self {
+                SortKey::Name => std::cmp::Ordering::Equal,
+                // Checks without data sort after every percentage
+                SortKey::Percentage => match (a.percentage, b.percentage) {
+                    (Some(a), Some(b)) => a.total_cmp(&b),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
+                },
+                SortKey::Downtime => b.downtime.cmp(&a.downtime),
+                SortKey::Id => a.id.cmp(&b.id),
+            };
+            by_key.then_with(|| (&a.name, &a.account, a.id).cmp(&(&b.name, &b.account, b.id)))
+        });
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DowntimeRounding {
+    /// Drop partial minutes
+    Floor,
+    /// Count any partial minute as a full minute
+    Ceil,
+    /// Round to the nearest minute
+    Round,
+}
+
+impl DowntimeRounding {
+    pub fn minutes(self, seconds: u64) -> u64 {
+        match self {
+            DowntimeRounding::Floor => seconds / 60,
+            DowntimeRounding::Ceil => seconds.div_ceil(60),
+            DowntimeRounding::Round => (seconds + 30) / 60,
+        }
+    }
+}
+
+/// Availability as a percentage with four decimals. The rounding is done on integers, in
+/// millionths of the window, so values like 99.99 come out exact instead of 99.99000000001.
+/// Only a window with no downtime at all is reported as 100; anything less stops at 99.9999.
+/// `None` when nothing was monitored (`max_uptime` is 0).
+pub fn uptime_percentage(uptime: u64, unmonitored: u64, max_uptime: u64) -> Option<f64> {
+    if max_uptime == 0 {
+        return None;
+    }
+    let available = (uptime + unmonitored) as u128;
+    let max_uptime = max_uptime as u128;
+    let mut millionths = (available * 1_000_000 + max_uptime / 2) / max_uptime;
+    if available < max_uptime {
+        millionths = millionths.min(999_999);
+    }
+    Some(millionths as f64 / 10000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::uptime_result;
+
+    #[test]
+    fn uptime_percentage_is_exact_at_the_boundaries() {
+        assert_eq!(uptime_percentage(10_000, 0, 10_000), Some(100.0));
+        assert_eq!(uptime_percentage(9_999, 0, 10_000), Some(99.99));
+        assert_eq!(uptime_percentage(9_990, 0, 10_000), Some(99.9));
+        assert_eq!(uptime_percentage(2, 0, 3), Some(66.6667));
+        assert_eq!(uptime_percentage(0, 0, 10_000), Some(0.0));
+    }
+
+    #[test]
+    fn uptime_percentage_counts_unmonitored_time_as_available() {
+        assert_eq!(uptime_percentage(9_000, 1_000, 10_000), Some(100.0));
+    }
+
+    #[test]
+    fn uptime_percentage_only_reaches_100_without_downtime() {
+        // A second down in a year rounds to 100 but is reported as 99.9999
+        assert_eq!(uptime_percentage(31_535_999, 0, 31_536_000), Some(99.9999));
+    }
+
+    #[test]
+    fn uptime_percentage_is_none_without_monitored_time() {
+        assert_eq!(uptime_percentage(0, 0, 0), None);
+    }
+
+    #[test]
+    fn report_with_an_unmonitored_check_round_trips() {
+        let checks = vec![uptime_result(1, "web", 9_990, 10), uptime_result(2, "new", 0, 0)];
+        let report = Report::new(DateTime::UNIX_EPOCH, DateTime::from_timestamp(10_000, 0).unwrap(), checks);
+        let path = std::env::temp_dir().join(format!("prt-report-round-trip-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_vec(&report).unwrap()).unwrap();
+
+        let loaded = Report::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.checks[0].percentage, Some(99.9));
+        assert_eq!(loaded.checks[1].percentage, None);
+        assert_eq!(loaded.aggregate.percentage, Some(99.9));
+        assert_eq!(loaded.aggregate.worst_check.map(|w| w.name), Some("web".to_string()));
+    }
+
+    /// The aggregate as JSON, which is what the report shows of it.
+    fn aggregate_of(checks: &[UptimeResult]) -> serde_json::Value {
+        let mut aggregate = Aggregate::from_checks(checks);
+        aggregate.statistics = Some(Statistics::from_checks(checks));
+        serde_json::to_value(aggregate).unwrap()
+    }
+
+    #[test]
+    fn aggregate_does_not_depend_on_completion_order() {
+        let checks = vec![
+            uptime_result(3, "db", 9_950, 50),
+            uptime_result(1, "web", 9_990, 10),
+            uptime_result(4, "cron", 9_950, 50),
+            uptime_result(2, "api", 10_000, 0),
+            uptime_result(5, "new", 0, 0),
+        ];
+        let mut reversed = checks.clone();
+        reversed.reverse();
+        let mut rotated = checks.clone();
+        rotated.rotate_left(2);
+
+        let aggregate = aggregate_of(&checks);
+        assert_eq!(aggregate_of(&reversed), aggregate);
+        assert_eq!(aggregate_of(&rotated), aggregate);
+        // db and cron share the lowest percentage; the name decides
+        assert_eq!(aggregate["worst_check"]["name"], "cron");
+        assert_eq!(aggregate["statistics"]["median"], 99.7);
+    }
+
+    #[test]
+    fn worst_check_ties_on_name_are_broken_by_id() {
+        let checks = [uptime_result(7, "web", 9_900, 100), uptime_result(3, "web", 9_900, 100), uptime_result(9, "web", 9_950, 50)];
+
+        for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            let ordered: Vec<UptimeResult> = order.iter().map(|&i| checks[i].clone()).collect();
+            assert_eq!(Aggregate::from_checks(&ordered).worst_check.unwrap().id, 3);
+        }
+    }
+
+    #[test]
+    fn sorted_checks_do_not_depend_on_completion_order() {
+        let in_account = |id, account: &str| UptimeResult { account: Some(account.to_string()), ..uptime_result(id, "web", 9_900, 100) };
+        let checks = vec![in_account(7, "us"), in_account(3, "us"), in_account(3, "eu"), uptime_result(1, "api", 10_000, 0)];
+        let order = |checks: &[UptimeResult]| checks.iter().map(|c| (c.id, c.account.clone())).collect::<Vec<_>>();
+
+        for key in [SortKey::Name, SortKey::Percentage, SortKey::Downtime, SortKey::Id] {
+            let mut forward = checks.clone();
+            let mut backward: Vec<UptimeResult> = checks.iter().rev().cloned().collect();
+            key.sort(&mut forward);
+            key.sort(&mut backward);
+            assert_eq!(order(&forward), order(&backward), "{:?}", key);
+        }
+    }
+}
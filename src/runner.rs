@@ -0,0 +1,964 @@
+//! The report pipeline: lists checks, calculates each one concurrently and gathers the results.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::stream::{self, StreamExt};
+use inquire::MultiSelect;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::time::{sleep, timeout, Duration};
+
+use crate::api::{check_tags, check_target, check_type, connection_failure, is_uptime_check, PingdomApi, Resolution, UptimeOptions};
+use crate::checkpoint::{self, Checkpoint};
+use crate::cli::ReportArgs;
+use crate::dates;
+use crate::error::PingdomError;
+use crate::limit::AdaptiveLimit;
+use crate::maintenance;
+use crate::report::{self, CostPolicy, ProbeUptime, Report, SlaPolicy, UptimeResult};
+
+/// How many checks of one account are calculated at the same time.
+const CHECK_CONCURRENCY: usize = 10;
+
+/// One Pingdom account to report on. `name` is only set in multi-account runs (`--account`) and
+/// is copied into each of its results.
+pub struct Account {
+    pub name: Option<String>,
+    pub api: PingdomApi,
+}
+
+/// Everything a single check calculation needs besides the check itself.
+struct RunContext<'a> {
+    args: &'a ReportArgs,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    uptime_from: String,
+    uptime_to: String,
+    probe_regions: HashMap<u64, String>,
+    sla_policy: SlaPolicy,
+    cost_policy: CostPolicy,
+    /// `--redact` labels by account name and check id; empty without the flag
+    redactions: HashMap<(Option<String>, u64), String>,
+    /// The `--maintenance` windows within this window
+    maintenance: Vec<(i64, i64)>,
+}
+
+/// Watches for Ctrl-C for the rest of the process: the first one sets the returned flag, a
+/// second one exits immediately. Installed once, before the first report run, and checked
+/// before every window and every check, so an interrupt also stops the windows still to come
+/// (`--compare-periods`, `--serve`) and one between windows is not lost.
+pub fn watch_interrupts() -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        flag.store(true, Ordering::Relaxed);
+        eprintln!("Interrupted: not starting any more checks (press Ctrl-C again to exit immediately)");
+        let _ = tokio::signal::ctrl_c().await;
+        std::process::exit(130);
+    });
+    interrupted
+}
+
+pub async fn run_report(
+    accounts: &[Account],
+    args: &ReportArgs,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    interrupted: &AtomicBool,
+) -> Result<Report, Box<dyn Error>> {
+    let mut reports = run_reports(accounts, args, &[(start_date, end_date)], interrupted).await?;
+    Ok(reports.pop().unwrap())
+}
+
+/// One report per window, all calculated at once (`--parallel-dates`).
+pub async fn run_reports(
+    accounts: &[Account],
+    args: &ReportArgs,
+    windows: &[Window],
+    interrupted: &AtomicBool,
+) -> Result<Vec<Report>, Box<dyn Error>> {
+    collect_windows(accounts, args, windows, interrupted, true, |_, _| Ok(())).await
+}
+
+/// For `--from-report`: applies this run's naming, SLA and cost flags to a saved report. Flags
+/// that were not given leave the saved annotations alone. The aggregate is recomputed, since
+/// the SLA failures and the worst check can change.
+pub fn reannotate(args: &ReportArgs, report: Report) -> Report {
+    let sla_policy = SlaPolicy { default: args.sla, per_tag: args.sla_per_tag.clone(), error_budget: args.error_budget };
+    let cost_policy = CostPolicy { default: args.cost_per_minute, per_tag: args.cost_per_tag.clone() };
+    let mut checks = report.checks;
+    for check in &mut checks {
+        check.name = args.normalize_name(&check.name);
+        if sla_policy.default.is_some() || !sla_policy.per_tag.is_empty() {
+            sla_policy.apply(check);
+        }
+        if cost_policy.default.is_some() || !cost_policy.per_tag.is_empty() {
+            cost_policy.apply(check);
+        }
+    }
+    Report { generated_at: report.generated_at, ..Report::new(report.from, report.to, checks) }
+}
+
+/// The check IDs and names given with `--checks` and `--checks-file`; empty means every check.
+fn wanted_checks(args: &ReportArgs) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut wanted: Vec<String> = args.checks.iter().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+    if let Some(path) = &args.checks_file {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read checks file {}: {}", path.display(), e))?;
+        for line in contents.lines() {
+            let entry = line.split('#').next().unwrap_or_default().trim();
+            if !entry.is_empty() {
+                wanted.push(entry.to_string());
+            }
+        }
+    }
+    Ok(wanted)
+}
+
+/// Warns, or fails with `--strict`, when `--resolution` would split the window into more buckets
+/// than one summary request should return.
+fn check_resolution(args: &ReportArgs, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+    let resolution = args.resolution;
+    let seconds = (end_date - start_date).num_seconds().max(0);
+    let buckets = (seconds + resolution.seconds() - 1) / resolution.seconds();
+    if buckets <= resolution.max_buckets() {
+        return Ok(());
+    }
+
+    let coarser = match resolution {
+        Resolution::Hour => " (try --resolution day or week)",
+        Resolution::Day => " (try --resolution week)",
+        Resolution::Week => "",
+    };
+    let message = format!(
+        "--resolution {} splits the window into {} buckets, more than the {} a single request should return{}",
+        resolution.param(),
+        buckets,
+        resolution.max_buckets(),
+        coarser
+    );
+    if args.strict {
+        return Err(message.into());
+    }
+    eprintln!("Warning: {}", message);
+    Ok(())
+}
+
+/// Keeps the checks matching an entry by ID or exact name, warning about entries that match
+/// nothing in any account. Every check is kept when nothing was asked for.
+fn select_checks<'a, 'b>(all_checks: Vec<(&'a Account, &'b Value)>, wanted: &[String]) -> Vec<(&'a Account, &'b Value)> {
+    if wanted.is_empty() {
+        return all_checks;
+    }
+
+    let matches = |c: &Value, entry: &str| {
+        c["name"].as_str() == Some(entry) || entry.parse::<u64>().is_ok_and(|id| c["id"].as_u64() == Some(id))
+    };
+    for entry in wanted {
+        if !all_checks.iter().any(|(_, c)| matches(c, entry)) {
+            eprintln!("Warning: no check matches '{}'", entry);
+        }
+    }
+    all_checks.into_iter().filter(|(_, c)| wanted.iter().any(|entry| matches(c, entry))).collect()
+}
+
+/// Narrows the checks down to the single one named by `--check-name`, ignoring case. Finding
+/// none or several is an error rather than a warning, since there would be nothing (or the wrong
+/// thing) to report.
+fn find_check_by_name<'a, 'b>(
+    all_checks: Vec<(&'a Account, &'b Value)>,
+    name: &str,
+) -> Result<Vec<(&'a Account, &'b Value)>, Box<dyn Error>> {
+    let found: Vec<_> = all_checks
+        .into_iter()
+        .filter(|(_, c)| c["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+        .collect();
+    match found.len() {
+        0 => Err(format!("no check is named '{}'", name).into()),
+        1 => Ok(found),
+        _ => {
+            let candidates: Vec<String> = found
+                .iter()
+                .map(|(account, c)| {
+                    let account = account.name.as_ref().map(|a| format!("{}/", a)).unwrap_or_default();
+                    format!("{}{} ({})", account, c["name"].as_str().unwrap_or_default(), c["id"])
+                })
+                .collect();
+            Err(format!("'{}' matches several checks: {}; use --checks with an ID instead", name, candidates.join(", ")).into())
+        }
+    }
+}
+
+/// `--pick`: lets the user tick checks in a list that filters as they type. Needs a terminal.
+fn pick_checks<'a, 'b>(all_checks: Vec<(&'a Account, &'b Value)>) -> Result<Vec<(&'a Account, &'b Value)>, Box<dyn Error>> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err("--pick needs an interactive terminal".into());
+    }
+    let mut all_checks = all_checks;
+    all_checks.sort_by_key(|(account, c)| (c["name"].as_str().unwrap_or_default().to_lowercase(), account.name.clone()));
+    let labels: Vec<String> = all_checks
+        .iter()
+        .map(|(account, c)| {
+            let account = account.name.as_ref().map(|a| format!("{}/", a)).unwrap_or_default();
+            format!("{}{} ({})", account, c["name"].as_str().unwrap_or_default(), c["id"])
+        })
+        .collect();
+    let picked = MultiSelect::new("Checks to report:", labels)
+        .with_help_message("type to filter, space to select, enter to confirm")
+        .with_page_size(15)
+        .raw_prompt()?;
+    if picked.is_empty() {
+        return Err("no checks were picked".into());
+    }
+    Ok(picked.iter().map(|option| all_checks[option.index]).collect())
+}
+
+/// The checks list of every account, fetched up to `parallel_accounts` at a time.
+async fn fetch_check_lists(accounts: &[Account], parallel_accounts: usize) -> Result<Vec<(&Account, Value)>, Box<dyn Error>> {
+    stream::iter(accounts)
+        .map(|account| async move {
+            let checks = account.api.get_checks().await?;
+            Ok::<_, Box<dyn Error>>((account, checks))
+        })
+        .buffered(parallel_accounts)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// The checks to calculate: the `--check-name` match, or those selected by `--checks` and
+/// `--checks-file`, minus the check types that have no uptime summary.
+fn choose_checks<'a, 'b>(
+    args: &ReportArgs,
+    check_lists: &'b [(&'a Account, Value)],
+    wanted: &[String],
+) -> Result<Vec<(&'a Account, &'b Value)>, Box<dyn Error>> {
+    let all_checks: Vec<(&Account, &Value)> = check_lists
+        .iter()
+        .flat_map(|(account, checks)| checks["checks"].as_array().into_iter().flatten().map(move |c| (*account, c)))
+        .collect();
+    let all_checks = match &args.check_name {
+        Some(name) => find_check_by_name(all_checks, name)?,
+        None if args.pick => pick_checks(all_checks)?,
+        None => select_checks(all_checks, wanted),
+    };
+    let (all_checks, unsupported): (Vec<_>, Vec<_>) = all_checks.into_iter().partition(|(_, c)| is_uptime_check(c));
+    for (_, c) in &unsupported {
+        eprintln!(
+            "Warning: skipping {} ({}): checks of type '{}' have no uptime summary",
+            c["name"].as_str().unwrap_or_default(),
+            c["id"],
+            check_type(c).unwrap_or("unknown")
+        );
+    }
+    Ok(all_checks)
+}
+
+/// A reporting window, from its start to its (exclusive) end
+type Window = (DateTime<Utc>, DateTime<Utc>);
+
+/// How far this machine's clock may run ahead of Pingdom's before it is worth a warning, and
+/// how far a window may end past Pingdom's current time before it is cut off there.
+const CLOCK_SKEW_TOLERANCE: TimeDelta = TimeDelta::minutes(5);
+
+/// Ends windows that reach past Pingdom's current time (from the `Date` header of its
+/// responses) at that time instead. Such a window comes from a local clock that runs ahead, or
+/// from an end date in the future; either way the tail would have no data. A skewed local
+/// clock is warned about, since it also shifts windows that end "now" (`--serve`) or "today".
+fn clamp_to_server_time(
+    windows: &[Window],
+    server_now: Option<DateTime<Utc>>,
+) -> Result<Vec<Window>, Box<dyn Error>> {
+    let Some(server_now) = server_now else {
+        return Ok(windows.to_vec());
+    };
+    let skew = Utc::now() - server_now;
+    if skew > CLOCK_SKEW_TOLERANCE {
+        eprintln!(
+            "Warning: this machine's clock is {} minutes ahead of Pingdom's ({}), check its time settings",
+            skew.num_minutes(),
+            server_now.to_rfc3339()
+        );
+    }
+    windows
+        .iter()
+        .map(|&(start_date, end_date)| {
+            if end_date <= server_now + CLOCK_SKEW_TOLERANCE {
+                return Ok((start_date, end_date));
+            }
+            if start_date >= server_now {
+                return Err(format!(
+                    "the window starts at {}, after Pingdom's current time {}",
+                    start_date.to_rfc3339(),
+                    server_now.to_rfc3339()
+                )
+                .into());
+            }
+            eprintln!(
+                "Warning: the window ends at {}, after Pingdom's current time; ending it at {} instead",
+                end_date.to_rfc3339(),
+                server_now.to_rfc3339()
+            );
+            Ok((start_date, server_now))
+        })
+        .collect()
+}
+
+/// Explains an empty selection, which would otherwise look like a successful empty report:
+/// either Pingdom listed no checks at all, or none of them survived the filters. Fails under
+/// `--strict`, warns otherwise.
+fn report_no_checks(args: &ReportArgs, check_lists: &[(&Account, Value)]) -> Result<(), Box<dyn Error>> {
+    let listed: Vec<&Value> = check_lists.iter().flat_map(|(_, checks)| checks["checks"].as_array().into_iter().flatten()).collect();
+    let message = if listed.is_empty() {
+        match check_lists.len() {
+            1 => "the account has no checks".to_string(),
+            n => format!("none of the {} accounts has any checks", n),
+        }
+    } else if !listed.iter().any(|c| is_uptime_check(c)) {
+        format!("none of the {} checks has an uptime summary", listed.len())
+    } else {
+        format!("the filters matched none of the {} checks", listed.len())
+    };
+    if args.strict {
+        return Err(format!("nothing to report: {}", message).into());
+    }
+    eprintln!("Warning: nothing to report: {}", message);
+    Ok(())
+}
+
+/// `--max-name-length`: fails when any selected check's name, as Pingdom has it, is longer than
+/// `max` characters, listing each one so it can be renamed at the source.
+fn check_name_lengths(checks: &[(&Account, &Value)], max: usize) -> Result<(), Box<dyn Error>> {
+    let offenders: Vec<String> = checks
+        .iter()
+        .filter_map(|(_, c)| {
+            let name = c["name"].as_str().unwrap_or_default();
+            let length = name.chars().count();
+            (length > max).then(|| {
+                let ellipsis = if length > 40 { "..." } else { "" };
+                let shown: String = name.chars().take(40).collect();
+                format!("{}{} ({}, {} characters)", shown, ellipsis, c["id"], length)
+            })
+        })
+        .collect();
+    if offenders.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "{} checks have names longer than {} characters (--max-name-length): {}",
+        offenders.len(),
+        max,
+        offenders.join(", ")
+    )
+    .into())
+}
+
+/// For `--redact`: `check-1`, `check-2`, ... in order of check id (then account), so a check
+/// keeps its label for as long as the selection does not change.
+fn redaction_labels(checks: &[(&Account, &Value)]) -> HashMap<(Option<String>, u64), String> {
+    let mut keys: Vec<(u64, Option<String>)> = checks
+        .iter()
+        .filter_map(|(account, c)| Some((c["id"].as_u64()?, account.name.clone())))
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .enumerate()
+        .map(|(i, (id, account))| ((account, id), format!("check-{}", i + 1)))
+        .collect()
+}
+
+/// One line of the `--redact-map` file.
+#[derive(Serialize)]
+struct RedactedCheck<'a> {
+    label: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<&'a str>,
+    id: u64,
+    name: &'a str,
+    target: Option<String>,
+}
+
+/// Writes the `--redact-map` file: a JSON array that turns each label back into the check, in
+/// label order.
+fn write_redact_map(
+    path: &Path,
+    checks: &[(&Account, &Value)],
+    labels: &HashMap<(Option<String>, u64), String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut entries: Vec<RedactedCheck> = checks
+        .iter()
+        .filter_map(|(account, c)| {
+            let id = c["id"].as_u64()?;
+            Some(RedactedCheck {
+                label: labels.get(&(account.name.clone(), id))?,
+                account: account.name.as_deref(),
+                id,
+                name: c["name"].as_str().unwrap_or_default(),
+                target: check_target(c),
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| (e.id, e.account));
+    let mut json = serde_json::to_string_pretty(&entries)?;
+    json.push('\n');
+    fs::write(path, json).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// The one check a single-check mode such as `--probe-breakdown` works on, with the account it
+/// belongs to.
+async fn single_check<'a>(accounts: &'a [Account], args: &ReportArgs, mode: &str) -> Result<(&'a Account, Value), Box<dyn Error>> {
+    let wanted = wanted_checks(args)?;
+    let check_lists = fetch_check_lists(accounts, args.parallel_accounts.get()).await?;
+    let checks = choose_checks(args, &check_lists, &wanted)?;
+    let [(account, c)] = checks[..] else {
+        return Err(format!("{} needs exactly one check (use --check or --check-name), {} selected", mode, checks.len()).into());
+    };
+    Ok((account, c.clone()))
+}
+
+/// For `--probe-breakdown`: the name of the one selected check and what each probe that tested
+/// it saw, most downtime first, with probe names and regions from `/probes`.
+pub async fn probe_breakdown(
+    accounts: &[Account],
+    args: &ReportArgs,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+) -> Result<(String, Vec<ProbeUptime>), Box<dyn Error>> {
+    let (account, c) = single_check(accounts, args, "--probe-breakdown").await?;
+
+    let (from, to) = (start_date.timestamp().to_string(), end_date.timestamp().to_string());
+    let check_id = c["id"].as_u64().unwrap_or_default();
+    let (probes, mut breakdown) = tokio::try_join!(
+        account.api.get_probes(),
+        account.api.calculate_probe_uptime(check_id, &from, &to, args.resolution, args.downtime_rounding),
+    )?;
+    for probe in &mut breakdown {
+        let listed = probes["probes"].as_array().into_iter().flatten().find(|p| p["id"].as_u64() == Some(probe.probe_id));
+        if let Some(listed) = listed {
+            probe.name = listed["name"].as_str().map(str::to_string);
+            probe.region = listed["region"].as_str().map(str::to_string);
+        }
+    }
+
+    Ok((args.normalize_name(c["name"].as_str().unwrap_or_default()), breakdown))
+}
+
+/// For `--raw`: Pingdom's performance summary of the one selected check exactly as it was
+/// returned, requested the way the report requests it. With `--raw-detail` the check detail
+/// from `/checks/{id}` is added, as `{"summary": ..., "detail": ...}`.
+pub async fn raw_dump(
+    accounts: &[Account],
+    args: &ReportArgs,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+) -> Result<Value, Box<dyn Error>> {
+    let (account, c) = single_check(accounts, args, "--raw").await?;
+
+    let (from, to) = (start_date.timestamp().to_string(), end_date.timestamp().to_string());
+    let check_id = c["id"].as_u64().unwrap_or_default();
+    let summary = account.api.get_perf_summary(check_id, &from, &to, "true", args.resolution.param(), None);
+    if !args.raw_detail {
+        return Ok(summary.await?);
+    }
+    let (summary, detail) = tokio::try_join!(summary, account.api.get_check(check_id))?;
+    Ok(serde_json::json!({ "summary": summary, "detail": detail }))
+}
+
+/// For `--probe-from`/`--probe-to`: the one selected check over an arbitrary window, with the
+/// figures of every hour in it, to verify an incident after an alert.
+pub async fn spot_check(accounts: &[Account], args: &ReportArgs, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<UptimeResult, Box<dyn Error>> {
+    let (account, c) = single_check(accounts, args, "--probe-from").await?;
+    let options = UptimeOptions {
+        with_outages: args.with_outages,
+        downtime_rounding: args.downtime_rounding,
+        resolution: Resolution::Hour,
+        include_uptime: true,
+        probes: None,
+        breakdown: true,
+        maintenance: maintenance::intervals(&args.maintenance, from, to, args.timezone),
+        exclude_unconfirmed: args.exclude_unconfirmed,
+    };
+    let check_id = c["id"].as_u64().unwrap_or_default();
+    let check_name = c["name"].as_str().unwrap_or_default();
+    let mut result = account
+        .api
+        .calculate_uptime(check_id, check_name, &from.timestamp().to_string(), &to.timestamp().to_string(), &options)
+        .await?;
+    result.account = account.name.clone();
+    Ok(result)
+}
+
+/// Calculates one check, including the optional alerting, region and probe-filter requests, all
+/// inside the same concurrency slot.
+async fn calculate_check(context: &RunContext<'_>, account: &Account, c: &Value) -> Result<UptimeResult, Box<dyn Error>> {
+    let RunContext { args, uptime_from, uptime_to, probe_regions, maintenance, .. } = context;
+    let pingdom_api = &account.api;
+    let check_name = c["name"].as_str().unwrap_or_default();
+    let check_id = c["id"]
+        .as_u64()
+        .ok_or_else(|| PingdomError::Parse(format!("the checks list has no numeric id for '{}': {}", check_name, c["id"])))?;
+
+    let mut options = UptimeOptions {
+        with_outages: args.with_outages,
+        downtime_rounding: args.downtime_rounding,
+        resolution: args.resolution,
+        include_uptime: args.include_uptime(),
+        probes: None,
+        breakdown: args.with_weekly_breakdown,
+        maintenance: maintenance.clone(),
+        exclude_unconfirmed: args.exclude_unconfirmed,
+    };
+    if let Some(region) = &args.probe_filter {
+        let probes = pingdom_api
+            .probes_by_region(check_id, uptime_from, uptime_to, probe_regions)
+            .await
+            .ok()
+            .and_then(|mut by_region| {
+                let key = by_region.keys().copied().find(|r| r.eq_ignore_ascii_case(region))?;
+                by_region.remove(key)
+            });
+        match probes {
+            Some(probes) => options.probes = Some(probes.join(",")),
+            None => eprintln!("Warning: no {} probe data for {}, using all probes", region, check_name),
+        }
+    }
+
+    let uptime = pingdom_api.calculate_uptime(check_id, check_name, uptime_from, uptime_to, &options);
+    let series = async {
+        match args.aggregate_by {
+            Some(period) => pingdom_api
+                .period_series(check_id, uptime_from, uptime_to, period, args.timezone, args.downtime_rounding)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    };
+    // The series covers the same window, so with --parallel-dates both are requested at once
+    let (mut uptime_calc, series) = if args.parallel_dates {
+        tokio::try_join!(uptime, series)?
+    } else {
+        (uptime.await?, series.await?)
+    };
+    uptime_calc.series = series;
+    if uptime_calc.incomplete_data {
+        eprintln!("Warning: incomplete performance data for {}, missing totals were counted as 0", check_name);
+    }
+    if args.exclude_unconfirmed && uptime_calc.unconfirmed_downtime.is_none() {
+        eprintln!("Warning: no outage states for {}, counting its total downtime (--exclude-unconfirmed)", check_name);
+    }
+    uptime_calc.name = args.normalize_name(&uptime_calc.name);
+    uptime_calc.account = account.name.clone();
+    uptime_calc.target = check_target(c);
+    uptime_calc.tags = check_tags(c).map(str::to_string).collect();
+    uptime_calc.last_down = dates::from_epoch(c["lastdownstart"].as_i64(), args.timezone);
+    uptime_calc.last_up = dates::from_epoch(c["lastdownend"].as_i64(), args.timezone);
+    uptime_calc.last_test = dates::from_epoch(c["lasttesttime"].as_i64(), args.timezone);
+    uptime_calc.created = dates::from_epoch(c["created"].as_i64(), args.timezone);
+    uptime_calc.created_in_window = uptime_calc.created.is_some_and(|t| t > context.start_date && t < context.end_date);
+    if let Some(min) = args.probe_count_min {
+        // The summary has no sample counts, so estimate them from the monitored time and the
+        // check's test interval (in minutes)
+        let interval = c["resolution"].as_u64().unwrap_or(1).max(1) * 60;
+        uptime_calc.low_confidence = Some((uptime_calc.uptime + uptime_calc.downtime) / interval < min);
+    }
+    context.sla_policy.apply(&mut uptime_calc);
+    if args.flag_anomalies {
+        report::flag_anomalies(&mut uptime_calc, args.anomaly_threshold);
+        // Only buckets with a percentage, of a check with one, are ever flagged
+        let flagged = uptime_calc.breakdown.iter().flatten().filter(|b| b.anomaly == Some(true));
+        for (bucket, (percentage, overall)) in flagged.filter_map(|b| Some((b, b.percentage.zip(uptime_calc.percentage)?))) {
+            eprintln!(
+                "Incident candidate: {}, bucket starting {}, {}% (check overall {}%)",
+                uptime_calc.name,
+                dates::display_date(bucket.start.date_naive(), args.date_format.as_deref()),
+                percentage,
+                overall
+            );
+        }
+    }
+    context.cost_policy.apply(&mut uptime_calc);
+
+    if args.with_alerting {
+        if let Err(e) = pingdom_api.fetch_alerting(&mut uptime_calc).await {
+            eprintln!("Warning: could not fetch alerting details for {}: {}", uptime_calc.name, e);
+        }
+    }
+    if args.response_trend {
+        match pingdom_api.response_trend(check_id, uptime_from, uptime_to, args.timezone).await {
+            Ok(trend) => uptime_calc.response_trend = Some(trend),
+            Err(e) => eprintln!("Warning: could not fetch the response trend for {}: {}", uptime_calc.name, e),
+        }
+    }
+    if args.by_region {
+        // Not every check type reports per-probe results; treat that as "no regional data"
+        let regions = pingdom_api
+            .calculate_region_uptime(check_id, uptime_from, uptime_to, args.resolution, probe_regions)
+            .await
+            .unwrap_or_default();
+        uptime_calc.regions = Some(regions);
+    }
+    if let Some(label) = context.redactions.get(&(account.name.clone(), check_id)) {
+        uptime_calc.name = label.clone();
+        uptime_calc.target = None;
+    }
+
+    Ok(uptime_calc)
+}
+
+/// Runs every check and passes each result to `on_result` as soon as it completes. Results are
+/// also returned, sorted by name, unless `keep` is false; then nothing beyond the in-flight
+/// concurrency window is held in memory.
+///
+/// Up to `--parallel-accounts` accounts are worked on at once, each with its own window of
+/// `CHECK_CONCURRENCY` checks.
+pub async fn collect_results<F>(
+    accounts: &[Account],
+    args: &ReportArgs,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    interrupted: &AtomicBool,
+    keep: bool,
+    mut on_result: F,
+) -> Result<Vec<UptimeResult>, Box<dyn Error>>
+where
+    F: FnMut(&UptimeResult) -> io::Result<()>,
+{
+    let mut reports = collect_windows(accounts, args, &[(start_date, end_date)], interrupted, keep, |_, u| on_result(u)).await?;
+    Ok(reports.pop().map(|r| r.checks).unwrap_or_default())
+}
+
+/// Like `collect_results`, for several windows at once: every (window, check) pair is one task
+/// in the same per-account concurrency window, so `--parallel-dates` spends no more of the
+/// rate budget than a single window would. `on_result` gets the index of the window, and one
+/// report comes back per window, in the order of `windows`, covering the window as clamped to
+/// Pingdom's clock.
+pub async fn collect_windows<F>(
+    accounts: &[Account],
+    args: &ReportArgs,
+    windows: &[Window],
+    interrupted: &AtomicBool,
+    keep: bool,
+    mut on_result: F,
+) -> Result<Vec<Report>, Box<dyn Error>>
+where
+    F: FnMut(usize, &UptimeResult) -> io::Result<()>,
+{
+    // A Ctrl-C during an earlier window also stops this one
+    if interrupted.load(Ordering::Relaxed) {
+        let (start_date, end_date) = windows.first().copied().unwrap_or_default();
+        return Err(format!(
+            "interrupted before the report for {} to {} started",
+            start_date.to_rfc3339(),
+            end_date.to_rfc3339()
+        )
+        .into());
+    }
+    let wanted = wanted_checks(args)?;
+    let parallel_accounts = args.parallel_accounts.get();
+
+    let check_lists = fetch_check_lists(accounts, parallel_accounts).await.map_err(|e| {
+        match connection_failure(e.as_ref()).filter(|_| args.fail_fast) {
+            Some(failure) => format!("aborting before the first check (--fail-fast): {}", failure).into(),
+            None => e,
+        }
+    })?;
+
+    // The check list came with Pingdom's idea of the current time. The checkpoint keeps the
+    // requested windows: a window ending in the future is cut off at a different time on every
+    // run, and would otherwise never match the one it was recorded for.
+    let requested = windows;
+    let windows = &clamp_to_server_time(windows, accounts.iter().find_map(|a| a.api.server_time()))?;
+    for (start_date, end_date) in windows {
+        check_resolution(args, *start_date, *end_date)?;
+    }
+
+    let all_checks = choose_checks(args, &check_lists, &wanted)?;
+    if all_checks.is_empty() {
+        report_no_checks(args, &check_lists)?;
+    }
+    if let Some(max) = args.max_name_length {
+        check_name_lengths(&all_checks, max as usize)?;
+    }
+    let redactions = if args.redact { redaction_labels(&all_checks) } else { HashMap::new() };
+    if let Some(path) = &args.redact_map {
+        write_redact_map(path, &all_checks, &redactions)?;
+    }
+
+    // Probes are the same for every account, so the first one is asked
+    let probe_regions: HashMap<u64, String> = match accounts.first() {
+        Some(account) if args.by_region || args.probe_filter.is_some() => {
+            let probes = account.api.get_probes().await?;
+            probes["probes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|p| Some((p["id"].as_u64()?, p["region"].as_str()?.to_string())))
+                .collect()
+        }
+        _ => HashMap::new(),
+    };
+
+    let contexts: Vec<RunContext> = windows
+        .iter()
+        .map(|&(start_date, end_date)| RunContext {
+            args,
+            start_date,
+            end_date,
+            uptime_from: start_date.timestamp().to_string(),
+            uptime_to: end_date.timestamp().to_string(),
+            probe_regions: probe_regions.clone(),
+            sla_policy: SlaPolicy {
+                default: args.sla,
+                per_tag: args.sla_per_tag.clone(),
+                error_budget: args.error_budget,
+            },
+            cost_policy: CostPolicy {
+                default: args.cost_per_minute,
+                per_tag: args.cost_per_tag.clone(),
+            },
+            redactions: redactions.clone(),
+            maintenance: maintenance::intervals(&args.maintenance, start_date, end_date, args.timezone),
+        })
+        .collect();
+    let contexts = &contexts;
+
+    let check_count = all_checks.len() * windows.len();
+    let mut by_account: Vec<(&Account, Vec<&Value>)> = Vec::new();
+    for (account, c) in all_checks {
+        match by_account.iter_mut().find(|(a, _)| std::ptr::eq(*a, account)) {
+            Some((_, checks)) => checks.push(c),
+            None => by_account.push((account, vec![c])),
+        }
+    }
+    let by_account: Vec<(&Account, Vec<&Value>, Option<AdaptiveLimit>)> = by_account
+        .into_iter()
+        .map(|(account, checks)| {
+            let limit = args.adaptive_concurrency.then(|| {
+                let label = account.name.as_ref().map(|name| format!(" for {}", name)).unwrap_or_default();
+                AdaptiveLimit::new(args.min_concurrency.get(), args.max_concurrency.get(), label, account.api.verbose())
+            });
+            (account, checks, limit)
+        })
+        .collect();
+    let per_account_concurrency = if args.adaptive_concurrency { args.max_concurrency.get() } else { CHECK_CONCURRENCY };
+
+    // What an earlier run with the same --checkpoint already finished is not calculated again
+    let resumed = match &args.checkpoint {
+        Some(path) => {
+            let resumed = checkpoint::load(path, requested)?;
+            let count: usize = resumed.iter().map(Vec::len).sum();
+            if count > 0 {
+                eprintln!("Resuming from checkpoint {}: {} of {} checks already done", path.display(), count, check_count);
+            }
+            resumed
+        }
+        None => windows.iter().map(|_| Vec::new()).collect(),
+    };
+    let done: HashSet<(usize, Option<String>, u64)> = resumed
+        .iter()
+        .enumerate()
+        .flat_map(|(window, checks)| checks.iter().map(move |u| (window, u.account.clone(), u.id)))
+        .collect();
+    let done = &done;
+    let checkpoint = args.checkpoint.as_deref().map(Checkpoint::open).transpose()?;
+    let checkpoint_writer = checkpoint.as_ref();
+
+    // On Ctrl-C stop starting new checks; buffer_unordered still drains the ones in flight
+    let mut uptime_calculations = stream::iter(&by_account)
+        .map(|(account, checks, limit)| {
+            let tasks = contexts
+                .iter()
+                .enumerate()
+                .flat_map(|(window, context)| checks.iter().map(move |c| (window, context, *c)))
+                .filter(|(window, _, c)| !done.contains(&(*window, account.name.clone(), c["id"].as_u64().unwrap_or_default())));
+            stream::iter(tasks.enumerate())
+                .take_while(|_| futures::future::ready(!interrupted.load(Ordering::Relaxed)))
+                .map(move |(index, (window, context, c))| async move {
+                    // Spread the first window out so it does not hit Pingdom as a single burst
+                    if let Some(ramp_ms) = args.ramp_ms {
+                        if index < per_account_concurrency {
+                            sleep(Duration::from_millis(ramp_ms * index as u64)).await;
+                        }
+                    }
+                    let _slot = match limit {
+                        Some(limit) => Some(limit.acquire().await),
+                        None => None,
+                    };
+                    let check_name = c["name"].as_str().unwrap_or_default();
+                    let retries = args.probe_timeout_retries;
+                    let mut attempt = 0;
+                    let result = loop {
+                        let work = calculate_check(context, account, c);
+                        let result = match args.timeout_per_check {
+                            Some(secs) => timeout(Duration::from_secs(secs), work)
+                                .await
+                                .unwrap_or_else(|_| Err(format!("timed out after {}s", secs).into())),
+                            None => work.await,
+                        };
+                        match &result {
+                            Err(e)
+                                if attempt < retries
+                                    && !(args.fail_fast && connection_failure(e.as_ref()).is_some())
+                                    && account.api.take_retry() =>
+                            {
+                                attempt += 1;
+                                account.api.record_check_retry();
+                                if account.api.verbose() {
+                                    eprintln!("Retrying {} ({}/{}): {}", check_name, attempt, retries, e);
+                                }
+                                sleep(Duration::from_secs(1)).await;
+                            }
+                            _ => break result,
+                        }
+                    };
+                    if let (Some(checkpoint), Ok(u)) = (checkpoint_writer, &result) {
+                        let (from, to) = requested[window];
+                        checkpoint.record(from, to, u);
+                    }
+                    match &result {
+                        Ok(_) if attempt > 0 && account.api.verbose() => {
+                            eprintln!("{} succeeded after {} retries", check_name, attempt);
+                        }
+                        Ok(_) => {}
+                        // Reported once, as the reason the run stops
+                        Err(e) if args.fail_fast && connection_failure(e.as_ref()).is_some() => {}
+                        Err(e) if attempt > 0 => {
+                            eprintln!(
+                                "Warning: could not calculate uptime for {} after {} retries: {}",
+                                check_name, attempt, e
+                            );
+                        }
+                        Err(e) => eprintln!("Warning: could not calculate uptime for {}: {}", check_name, e),
+                    }
+                    sleep(Duration::from_millis(200)).await; // Add a small delay to avoid rate limiting
+                    if let Some(limit) = limit {
+                        limit.record(account.api.stats().rate_limited);
+                    }
+                    (window, result)
+                })
+                .buffer_unordered(per_account_concurrency)
+        })
+        .flatten_unordered(parallel_accounts);
+
+    let mut results: Vec<Vec<UptimeResult>> = windows.iter().map(|_| Vec::new()).collect();
+    let (mut completed, mut failed) = (0, 0);
+    let outcome: Result<(), Box<dyn Error>> = async {
+        for (window, checks) in resumed.into_iter().enumerate() {
+            for uptime_calc in checks {
+                completed += 1;
+                on_result(window, &uptime_calc)?;
+                if keep {
+                    results[window].push(uptime_calc);
+                }
+            }
+        }
+        while let Some((window, result)) = uptime_calculations.next().await {
+            match result {
+                Ok(uptime_calc) => {
+                    completed += 1;
+                    on_result(window, &uptime_calc)?;
+                    if keep {
+                        results[window].push(uptime_calc);
+                    }
+                }
+                Err(e) => {
+                    if let Some(failure) = connection_failure(e.as_ref()).filter(|_| args.fail_fast) {
+                        return Err(format!(
+                            "aborting after {} of {} checks (--fail-fast): {}",
+                            completed + failed,
+                            check_count,
+                            failure
+                        )
+                        .into());
+                    }
+                    failed += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+    // Whatever finished is kept for the next run, even when this one stops with an error
+    drop(uptime_calculations);
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.finish().await?;
+    }
+    outcome?;
+
+    for window in &mut results {
+        window.sort_by(|a, b| (&a.name, &a.account).cmp(&(&b.name, &b.account)));
+    }
+
+    if interrupted.load(Ordering::Relaxed) {
+        eprintln!(
+            "Warning: run was interrupted, the report only covers {} of {} checks",
+            completed, check_count
+        );
+    }
+
+    if args.strict && failed > 0 {
+        return Err(format!("{} checks could not be calculated", failed).into());
+    }
+
+    Ok(windows.iter().zip(results).map(|(&(start_date, end_date), checks)| Report::new(start_date, end_date, checks)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use crate::testing;
+    use axum::{routing::get, Json, Router};
+    use clap::Parser;
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn checkpoint_resumes_a_window_that_ends_in_the_future() {
+        let summaries = Arc::new(AtomicUsize::new(0));
+        let counter = summaries.clone();
+        let app = Router::new()
+            .route(
+                "/checks",
+                get(|| async { Json(json!({"checks": [{"id": 1, "name": "web", "type": "http", "hostname": "example.com"}]})) }),
+            )
+            .route(
+                "/summary.performance/{id}",
+                get(move || async move {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    Json(json!({"summary": {"hours": [{"starttime": 0, "uptime": 3600, "downtime": 0, "unmonitored": 0}]}}))
+                }),
+            );
+        let url = testing::serve(app).await;
+        let accounts = [Account { name: None, api: testing::client(&url) }];
+        let path = std::env::temp_dir().join(format!("prt-checkpoint-resume-{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let args = Cli::try_parse_from(["pingdom", "--checkpoint", path.to_str().unwrap()]).unwrap().report;
+
+        // The window is cut off at the server's time, which has moved on by the second run
+        let (start_date, end_date) = (Utc::now() - TimeDelta::days(1), Utc::now() + TimeDelta::days(1));
+        let interrupted = AtomicBool::new(false);
+        let first = run_report(&accounts, &args, start_date, end_date, &interrupted).await.unwrap();
+        sleep(Duration::from_millis(1100)).await;
+        let second = run_report(&accounts, &args, start_date, end_date, &interrupted).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let second = second.unwrap();
+        assert_eq!(summaries.load(Ordering::Relaxed), 1);
+        assert_eq!(first.checks.len(), 1);
+        assert_eq!(second.checks.len(), 1);
+        assert_eq!(second.checks[0].id, 1);
+    }
+}
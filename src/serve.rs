@@ -0,0 +1,77 @@
+//! Long-running exporter mode: recomputes the report on an interval and serves the latest
+//! results as Prometheus metrics at `/metrics`.
+
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use chrono::{Days, Utc};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::time::{sleep, Duration};
+
+use crate::cli::ReportArgs;
+use crate::runner::{run_report, Account};
+use crate::{dates, output};
+
+type Metrics = Arc<RwLock<Option<String>>>;
+
+async fn metrics(State(metrics): State<Metrics>) -> (StatusCode, String) {
+    match metrics.read().unwrap().as_ref() {
+        Some(body) => (StatusCode::OK, body.clone()),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "first report run has not completed yet\n".to_string()),
+    }
+}
+
+pub async fn serve(
+    accounts: Vec<Account>,
+    args: &ReportArgs,
+    listen: SocketAddr,
+    interrupted: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let latest: Metrics = Arc::new(RwLock::new(None));
+
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(latest.clone());
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    eprintln!("Serving metrics on http://{}/metrics", listen);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("Metrics server stopped: {}", e);
+        }
+    });
+
+    loop {
+        // Fixed dates are re-queried as given; otherwise use a window ending now
+        let (start_date, end_date) = match (&args.start_date, &args.end_date) {
+            (Some(start_date), Some(end_date)) => (
+                dates::start_of_day(dates::parse_date(start_date, args.timezone)?, args.timezone),
+                dates::start_of_day(dates::parse_date(end_date, args.timezone)?, args.timezone),
+            ),
+            _ => {
+                let now = Utc::now();
+                (now - Days::new(args.window_days), now)
+            }
+        };
+
+        let run = run_report(&accounts, args, start_date, end_date, interrupted).await;
+        // A run cut short by Ctrl-C is not published; the exporter stops instead
+        if interrupted.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        match run {
+            Ok(report) => {
+                let mut body = Vec::new();
+                output::write_prometheus(&mut body, &report, Utc::now())?;
+                *latest.write().unwrap() = Some(String::from_utf8(body)?);
+            }
+            // Keep serving the previous results until the next run succeeds
+            Err(e) => eprintln!("Report run failed: {}", e),
+        }
+
+        tokio::select! {
+            _ = sleep(Duration::from_secs(args.interval_secs)) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
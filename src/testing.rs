@@ -0,0 +1,38 @@
+//! Helpers shared by the unit tests: a stand-in for the Pingdom API, served by axum on a local
+//! port, a client pointed at it, and hand-built results.
+
+use axum::Router;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::api::{ClientOptions, PingdomApi};
+use crate::report::{uptime_percentage, UptimeResult};
+
+/// Serves `app` on an unused local port for the rest of the test and returns its base URL.
+pub async fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", address)
+}
+
+/// A client for the API at `url` with the default options.
+pub fn client(url: &str) -> PingdomApi {
+    PingdomApi::new("test-key", url, &ClientOptions::default()).unwrap()
+}
+
+/// A check monitored for its whole window, `uptime` seconds up and `downtime` seconds down,
+/// with every optional field left out.
+pub fn uptime_result(id: u64, name: &str, uptime: u64, downtime: u64) -> UptimeResult {
+    serde_json::from_value(json!({
+        "id": id,
+        "name": name,
+        "uptime": uptime,
+        "downtime": downtime,
+        "unmonitored": 0,
+        "max_uptime": uptime + downtime,
+        "percentage": uptime_percentage(uptime, 0, uptime + downtime),
+        "downtime_minutes": downtime / 60,
+    }))
+    .unwrap()
+}